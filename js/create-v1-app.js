@@ -1,14 +1,23 @@
 #!/usr/bin/env node
 
-const { run } = require('./index');
+const { runAsync, cancel } = require('./index');
 
 // Remove the first two arguments (node executable and script path)
 const args = process.argv.slice(2);
 
-console.log("Forwarding arguments to Rust:", args);
+// The run happens on a worker thread, so Ctrl-C can roll it back like the
+// binary does. A second one exits right away.
+let cancelling = false;
+process.on('SIGINT', () => {
+    if (cancelling) {
+        process.exit(130);
+    }
+    cancelling = true;
+    console.error("Cancelling, press Ctrl-C again to exit immediately");
+    cancel();
+});
 
-try {
-    run(args);
-} catch (error) {
+runAsync(args).catch((error) => {
     console.error("Error running create-v1-app:", error);
-}
\ No newline at end of file
+    process.exitCode = error.code === 'E_CANCELLED' ? 130 : 1;
+});
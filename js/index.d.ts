@@ -3,4 +3,117 @@
 
 /* auto-generated by NAPI-RS */
 
+/**
+ * An event as printed by `--output json`, e.g. `file_written` or
+ * `install_output`. Only the fields of its kind are set.
+ */
+export interface ProgressEvent {
+  event: string
+  /** The workspace or service. */
+  name?: string
+  path?: string
+  /** The template a file was written from or skipped. */
+  source?: string
+  /** Why a file was skipped. */
+  reason?: string
+  files_written?: number
+  package_manager?: string
+  /** `stdout` or `stderr` of the install. */
+  stream?: string
+  line?: string
+  success?: boolean
+  duration_ms?: number
+  /** The services selected, once resolved. */
+  selected?: Array<string>
+  /** Pairs of a service added as a requirement and the service requiring it. */
+  required?: Array<Array<string>>
+  skipped?: Array<string>
+  suggested?: Array<string>
+}
+export interface CreateAppOptions {
+  name: string
+  /** The directory the project is created in, the current one by default. */
+  directory?: string
+  services?: Array<string>
+  /** npm by default. */
+  packageManager?: string
+  /** The npm scope of the packages, `v1` by default. */
+  scope?: string
+  providers?: Array<string>
+  /** Extra variables passed to the templates. */
+  variables?: Record<string, any>
+  /** A directory to load the templates from instead of the bundled ones. */
+  template?: string
+  /** Installs the dependencies once the project is generated. */
+  install?: boolean
+  /**
+   * Initializes a git repository and commits the project, unless it is
+   * generated inside a git work tree.
+   */
+  git?: boolean
+  commitMessage?: string
+}
+export interface CreateAppResult {
+  projectDir: string
+  /** The services generated, including the ones added as requirements. */
+  services: Array<string>
+  files: Array<string>
+  durationMs: number
+}
+export interface ServiceInfo {
+  name: string
+  description: string
+  installed: boolean
+  requires: Array<string>
+  conflicts: Array<string>
+  suggests: Array<string>
+}
+/**
+ * Stops the `createAppAsync` or `addServicesAsync` call it was passed to.
+ * What was written is rolled back and the call rejects with `E_CANCELLED`.
+ */
+export class CancellationToken {
+  constructor()
+  cancel(): void
+  get isCancelled(): boolean
+}
+/**
+ * Runs the CLI with `args`, without the node executable and script path.
+ * Failures are thrown as errors with the stable error code as `code`.
+ */
 export declare function run(args: Array<string>): void
+/**
+ * Cancels everything running in the process, as ctrl+c does for the CLI.
+ * Calls started afterwards aren't affected.
+ */
+export declare function cancel(): void
+/** Generates a new project without prompting. */
+export declare function createApp(options: CreateAppOptions): CreateAppResult
+/** Runs the CLI with `args` on a worker thread, so `cancel` can stop it. */
+export declare function runAsync(args: Array<string>): Promise<void>
+/**
+ * Generates a new project on a worker thread, calling `onProgress` with
+ * every event as it happens. Cancelling `token` rolls it back.
+ */
+export declare function createAppAsync(options: CreateAppOptions, onProgress?: ((event: ProgressEvent) => void) | null, token?: CancellationToken): Promise<CreateAppResult>
+/**
+ * Adds services and the ones they require to an existing project without
+ * prompting. Returns the services added.
+ */
+export declare function addServices(projectDir: string, services: Array<string>): Array<string>
+/**
+ * Adds services to an existing project on a worker thread, calling
+ * `onProgress` with every event as it happens. Cancelling `token` rolls it
+ * back.
+ */
+export declare function addServicesAsync(projectDir: string, services: Array<string>, onProgress?: ((event: ProgressEvent) => void) | null, token?: CancellationToken): Promise<Array<string>>
+/**
+ * Every service that can be added, marked as installed when a project
+ * directory is given.
+ */
+export declare function listServices(projectDir?: string | undefined | null): Array<ServiceInfo>
+/**
+ * Removes an installed service from a project, with its imports and
+ * environment variables.
+ */
+export declare function removeService(projectDir: string, service: string): void
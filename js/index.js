@@ -310,6 +310,15 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { run } = nativeBinding
+const { CancellationToken, run, cancel, createApp, runAsync, createAppAsync, addServices, addServicesAsync, listServices, removeService } = nativeBinding
 
+module.exports.CancellationToken = CancellationToken
 module.exports.run = run
+module.exports.cancel = cancel
+module.exports.createApp = createApp
+module.exports.runAsync = runAsync
+module.exports.createAppAsync = createAppAsync
+module.exports.addServices = addServices
+module.exports.addServicesAsync = addServicesAsync
+module.exports.listServices = listServices
+module.exports.removeService = removeService
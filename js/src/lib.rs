@@ -3,7 +3,475 @@
 #[macro_use]
 extern crate napi_derive;
 
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+};
+
+use create_v1_app::{with_cancellation, Scaffold, ScaffoldReport, Service};
+use napi::{
+  bindgen_prelude::AsyncTask,
+  threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode},
+  Env, JsError, JsFunction, JsUnknown, NapiValue, Task,
+};
+use serde::Deserialize;
+
+type ProgressCallback = ThreadsafeFunction<ProgressEvent, ErrorStrategy::Fatal>;
+
+/// Turns an error into a JS error whose `code` is the stable error code,
+/// e.g. `E_PROJECT_EXISTS`.
+fn to_js_error(env: Env, error: anyhow::Error) -> napi::Error {
+  let error = napi::Error::new(create_v1_app::error_code(&error), error.to_string());
+  // SAFETY: the value was just created in this env
+  let value =
+    unsafe { JsUnknown::from_raw_unchecked(env.raw(), JsError::from(error).into_value(env.raw())) };
+  napi::Error::from(value)
+}
+
+fn parse_services(services: &[String]) -> anyhow::Result<Vec<Service>> {
+  services
+    .iter()
+    .map(|service| service.parse().map_err(create_v1_app::invalid_input))
+    .collect()
+}
+
+fn progress_callback(on_progress: Option<JsFunction>) -> napi::Result<Option<ProgressCallback>> {
+  on_progress
+    .map(|callback| callback.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value])))
+    .transpose()
+}
+
+/// Runs `f`, sending the events it emits to the JS callback if any.
+fn with_progress<T>(
+  on_progress: Option<ProgressCallback>,
+  f: impl FnOnce() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+  match on_progress {
+    Some(callback) => create_v1_app::with_progress(
+      move |event| {
+        if let Ok(event) = ProgressEvent::deserialize(event) {
+          callback.call(event, ThreadsafeFunctionCallMode::NonBlocking);
+        }
+      },
+      f,
+    ),
+    None => f(),
+  }
+}
+
+/// An event as printed by `--output json`, e.g. `file_written` or
+/// `install_output`. Only the fields of its kind are set.
+#[napi(object)]
+#[derive(Deserialize)]
+pub struct ProgressEvent {
+  pub event: String,
+  /// The workspace or service.
+  pub name: Option<String>,
+  pub path: Option<String>,
+  /// The template a file was written from or skipped.
+  pub source: Option<String>,
+  /// Why a file was skipped.
+  pub reason: Option<String>,
+  #[napi(js_name = "files_written")]
+  pub files_written: Option<u32>,
+  #[napi(js_name = "package_manager")]
+  pub package_manager: Option<String>,
+  /// `stdout` or `stderr` of the install.
+  pub stream: Option<String>,
+  pub line: Option<String>,
+  pub success: Option<bool>,
+  #[napi(js_name = "duration_ms")]
+  pub duration_ms: Option<f64>,
+  /// The services selected, once resolved.
+  pub selected: Option<Vec<String>>,
+  /// Pairs of a service added as a requirement and the service requiring it.
+  pub required: Option<Vec<Vec<String>>>,
+  pub skipped: Option<Vec<String>>,
+  pub suggested: Option<Vec<String>>,
+}
+
+#[napi(object)]
+pub struct CreateAppOptions {
+  pub name: String,
+  /// The directory the project is created in, the current one by default.
+  pub directory: Option<String>,
+  pub services: Option<Vec<String>>,
+  /// npm by default.
+  pub package_manager: Option<String>,
+  /// The npm scope of the packages, `v1` by default.
+  pub scope: Option<String>,
+  pub providers: Option<Vec<String>>,
+  /// Extra variables passed to the templates.
+  pub variables: Option<serde_json::Map<String, serde_json::Value>>,
+  /// A directory to load the templates from instead of the bundled ones.
+  pub template: Option<String>,
+  /// Installs the dependencies once the project is generated.
+  pub install: Option<bool>,
+  /// Initializes a git repository and commits the project, unless it is
+  /// generated inside a git work tree.
+  pub git: Option<bool>,
+  pub commit_message: Option<String>,
+}
+
+#[napi(object)]
+pub struct CreateAppResult {
+  pub project_dir: String,
+  /// The services generated, including the ones added as requirements.
+  pub services: Vec<String>,
+  pub files: Vec<String>,
+  pub duration_ms: f64,
+}
+
+#[napi(object)]
+pub struct ServiceInfo {
+  pub name: String,
+  pub description: String,
+  pub installed: bool,
+  pub requires: Vec<String>,
+  pub conflicts: Vec<String>,
+  pub suggests: Vec<String>,
+}
+
+/// Stops the `createAppAsync` or `addServicesAsync` call it was passed to.
+/// What was written is rolled back and the call rejects with `E_CANCELLED`.
+#[napi(js_name = "CancellationToken")]
+#[derive(Default)]
+pub struct JsCancellationToken {
+  token: create_v1_app::CancellationToken,
+}
+
 #[napi]
-pub fn run() -> napi::Result<()> {
-  create_v1_app::run().map_err(|e| napi::Error::from_reason(e.to_string()))
+impl JsCancellationToken {
+  #[napi(constructor)]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  #[napi]
+  pub fn cancel(&self) {
+    self.token.cancel();
+  }
+
+  #[napi(getter)]
+  pub fn is_cancelled(&self) -> bool {
+    self.token.is_cancelled()
+  }
+}
+
+fn names(services: &[Service]) -> Vec<String> {
+  services.iter().map(|s| s.to_string()).collect()
+}
+
+/// Runs the CLI with `args`, without the node executable and script path.
+/// Failures are thrown as errors with the stable error code as `code`.
+#[napi]
+pub fn run(env: Env, args: Vec<String>) -> napi::Result<()> {
+  create_v1_app::run_with_args(args).map_err(|e| to_js_error(env, e))
+}
+
+/// Cancels everything running in the process, as ctrl+c does for the CLI.
+/// Calls started afterwards aren't affected.
+#[napi]
+pub fn cancel() {
+  create_v1_app::cancel();
+}
+
+fn create_app_report(
+  options: CreateAppOptions,
+  cancellation: create_v1_app::CancellationToken,
+) -> anyhow::Result<ScaffoldReport> {
+  let mut scaffold = Scaffold::new(options.name)
+    .cancellation(cancellation)
+    .services(parse_services(&options.services.unwrap_or_default())?)
+    .providers(options.providers.unwrap_or_default())
+    .variables(BTreeMap::from_iter(options.variables.unwrap_or_default()))
+    .install(options.install.unwrap_or_default())
+    .git(options.git.unwrap_or_default());
+  if let Some(package_manager) = options.package_manager {
+    scaffold = scaffold.package_manager(package_manager);
+  }
+  if let Some(scope) = options.scope {
+    scaffold = scaffold.scope(scope);
+  }
+  if let Some(template) = options.template {
+    scaffold = scaffold.template(template);
+  }
+  if let Some(message) = options.commit_message {
+    scaffold = scaffold.commit_message(message);
+  }
+
+  let directory = match options.directory {
+    Some(directory) => PathBuf::from(directory),
+    None => std::env::current_dir()?,
+  };
+  scaffold.generate(&directory)
+}
+
+impl From<ScaffoldReport> for CreateAppResult {
+  fn from(report: ScaffoldReport) -> Self {
+    CreateAppResult {
+      project_dir: report.project_dir.display().to_string(),
+      services: names(&report.services),
+      files: report
+        .files
+        .iter()
+        .map(|file| file.display().to_string())
+        .collect(),
+      duration_ms: report.duration.as_secs_f64() * 1000.0,
+    }
+  }
+}
+
+/// Generates a new project without prompting.
+#[napi]
+pub fn create_app(env: Env, options: CreateAppOptions) -> napi::Result<CreateAppResult> {
+  create_app_report(options, Default::default())
+    .map(CreateAppResult::from)
+    .map_err(|e| to_js_error(env, e))
+}
+
+/// Keeps the error of `compute` so `reject` can throw it with its code,
+/// which needs an env.
+fn keep_error(slot: &mut Option<anyhow::Error>, error: anyhow::Error) -> napi::Error {
+  let reason = error.to_string();
+  *slot = Some(error);
+  napi::Error::from_reason(reason)
+}
+
+pub struct RunTask {
+  args: Vec<String>,
+  error: Option<anyhow::Error>,
+}
+
+#[napi]
+impl Task for RunTask {
+  type Output = ();
+  type JsValue = ();
+
+  fn compute(&mut self) -> napi::Result<()> {
+    create_v1_app::run_with_args(self.args.clone()).map_err(|e| keep_error(&mut self.error, e))
+  }
+
+  fn resolve(&mut self, _env: Env, _output: ()) -> napi::Result<()> {
+    Ok(())
+  }
+
+  fn reject(&mut self, env: Env, err: napi::Error) -> napi::Result<()> {
+    Err(self.error.take().map_or(err, |e| to_js_error(env, e)))
+  }
+}
+
+/// Runs the CLI with `args` on a worker thread, so `cancel` can stop it.
+#[napi]
+pub fn run_async(args: Vec<String>) -> AsyncTask<RunTask> {
+  AsyncTask::new(RunTask { args, error: None })
+}
+
+pub struct CreateAppTask {
+  options: Option<CreateAppOptions>,
+  on_progress: Option<ProgressCallback>,
+  cancellation: create_v1_app::CancellationToken,
+  error: Option<anyhow::Error>,
+}
+
+#[napi]
+impl Task for CreateAppTask {
+  type Output = ScaffoldReport;
+  type JsValue = CreateAppResult;
+
+  fn compute(&mut self) -> napi::Result<ScaffoldReport> {
+    let options = self.options.take().expect("the task runs once");
+    let cancellation = self.cancellation.clone();
+    with_progress(self.on_progress.take(), || {
+      create_app_report(options, cancellation)
+    })
+    .map_err(|e| keep_error(&mut self.error, e))
+  }
+
+  fn resolve(&mut self, _env: Env, report: ScaffoldReport) -> napi::Result<CreateAppResult> {
+    Ok(report.into())
+  }
+
+  fn reject(&mut self, env: Env, err: napi::Error) -> napi::Result<CreateAppResult> {
+    Err(self.error.take().map_or(err, |e| to_js_error(env, e)))
+  }
+}
+
+/// Generates a new project on a worker thread, calling `onProgress` with
+/// every event as it happens. Cancelling `token` rolls it back.
+#[napi(
+  ts_args_type = "options: CreateAppOptions, onProgress?: ((event: ProgressEvent) => void) | null, token?: CancellationToken"
+)]
+pub fn create_app_async(
+  options: CreateAppOptions,
+  on_progress: Option<JsFunction>,
+  token: Option<&JsCancellationToken>,
+) -> napi::Result<AsyncTask<CreateAppTask>> {
+  Ok(AsyncTask::new(CreateAppTask {
+    options: Some(options),
+    on_progress: progress_callback(on_progress)?,
+    cancellation: token.map(|t| t.token.clone()).unwrap_or_default(),
+    error: None,
+  }))
+}
+
+fn add_project_services(project_dir: &str, services: &[String]) -> anyhow::Result<Vec<String>> {
+  let services = parse_services(services)?;
+  let added = create_v1_app::add_services(
+    Path::new(project_dir),
+    &create_v1_app::bundled_templates_path(),
+    &services,
+  )?;
+  Ok(names(&added))
+}
+
+/// Adds services and the ones they require to an existing project without
+/// prompting. Returns the services added.
+#[napi]
+pub fn add_services(
+  env: Env,
+  project_dir: String,
+  services: Vec<String>,
+) -> napi::Result<Vec<String>> {
+  add_project_services(&project_dir, &services).map_err(|e| to_js_error(env, e))
+}
+
+pub struct AddServicesTask {
+  project_dir: String,
+  services: Vec<String>,
+  on_progress: Option<ProgressCallback>,
+  cancellation: create_v1_app::CancellationToken,
+  error: Option<anyhow::Error>,
+}
+
+#[napi]
+impl Task for AddServicesTask {
+  type Output = Vec<String>;
+  type JsValue = Vec<String>;
+
+  fn compute(&mut self) -> napi::Result<Vec<String>> {
+    let (project_dir, services) = (&self.project_dir, &self.services);
+    with_progress(self.on_progress.take(), || {
+      with_cancellation(&self.cancellation, || {
+        add_project_services(project_dir, services)
+      })
+    })
+    .map_err(|e| keep_error(&mut self.error, e))
+  }
+
+  fn resolve(&mut self, _env: Env, added: Vec<String>) -> napi::Result<Vec<String>> {
+    Ok(added)
+  }
+
+  fn reject(&mut self, env: Env, err: napi::Error) -> napi::Result<Vec<String>> {
+    Err(self.error.take().map_or(err, |e| to_js_error(env, e)))
+  }
+}
+
+/// Adds services to an existing project on a worker thread, calling
+/// `onProgress` with every event as it happens. Cancelling `token` rolls it
+/// back.
+#[napi(
+  ts_args_type = "projectDir: string, services: Array<string>, onProgress?: ((event: ProgressEvent) => void) | null, token?: CancellationToken"
+)]
+pub fn add_services_async(
+  project_dir: String,
+  services: Vec<String>,
+  on_progress: Option<JsFunction>,
+  token: Option<&JsCancellationToken>,
+) -> napi::Result<AsyncTask<AddServicesTask>> {
+  Ok(AsyncTask::new(AddServicesTask {
+    project_dir,
+    services,
+    on_progress: progress_callback(on_progress)?,
+    cancellation: token.map(|t| t.token.clone()).unwrap_or_default(),
+    error: None,
+  }))
+}
+
+/// Every service that can be added, marked as installed when a project
+/// directory is given.
+#[napi]
+pub fn list_services(project_dir: Option<String>) -> Vec<ServiceInfo> {
+  create_v1_app::list_services(project_dir.as_deref().map(Path::new))
+    .into_iter()
+    .map(|info| ServiceInfo {
+      name: info.name.to_string(),
+      description: info.description,
+      installed: info.installed,
+      requires: names(&info.requires),
+      conflicts: names(&info.conflicts),
+      suggests: names(&info.suggests),
+    })
+    .collect()
+}
+
+/// Removes an installed service from a project, with its imports and
+/// environment variables.
+#[napi]
+pub fn remove_service(env: Env, project_dir: String, service: String) -> napi::Result<()> {
+  parse_services(&[service])
+    .and_then(|services| create_v1_app::remove_services(Path::new(&project_dir), &services))
+    .map_err(|e| to_js_error(env, e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use create_v1_app::error_code;
+
+  fn options(name: &str, directory: &Path) -> CreateAppOptions {
+    CreateAppOptions {
+      name: name.to_string(),
+      directory: Some(directory.display().to_string()),
+      services: None,
+      package_manager: None,
+      scope: None,
+      providers: None,
+      variables: None,
+      template: Some(directory.join("templates").display().to_string()),
+      install: None,
+      git: None,
+      commit_message: None,
+    }
+  }
+
+  #[test]
+  fn test_errors_carry_their_codes() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    std::fs::create_dir_all(dir.join("templates")).unwrap();
+    std::fs::create_dir_all(dir.join("taken")).unwrap();
+    std::fs::write(dir.join("taken/README.md"), "").unwrap();
+    let cancelled = create_v1_app::CancellationToken::new();
+    cancelled.cancel();
+
+    let code = |result: anyhow::Result<()>| error_code(&result.unwrap_err());
+    let project_dir = dir.display().to_string();
+
+    assert_eq!(
+      code(add_project_services(&project_dir, &["nope".to_string()]).map(drop)),
+      "E_INVALID_INPUT"
+    );
+    assert_eq!(
+      code(add_project_services(&project_dir, &["cal".to_string()]).map(drop)),
+      "E_NOT_A_PROJECT"
+    );
+    assert_eq!(
+      code(create_app_report(options("taken", dir), Default::default()).map(drop)),
+      "E_PROJECT_EXISTS"
+    );
+    assert_eq!(
+      code(create_app_report(options("acme", dir), cancelled).map(drop)),
+      "E_CANCELLED"
+    );
+    assert_eq!(
+      code(create_v1_app::run_with_args(["add", "provider", "stripe"])),
+      "E_INVALID_INPUT"
+    );
+    assert_eq!(
+      code(create_v1_app::run_with_args(["new", "--bogus"])),
+      "E_INVALID_INPUT"
+    );
+  }
 }
@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::{cli::Service, error::invalid_input, logger::log_warn};
+
+/// Every value the CLI would otherwise ask for, supplied up front.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Answers {
+    /// Version of the CLI that recorded the answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<Service>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package_manager: Option<String>,
+    /// npm scope of the generated packages, without the `@`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Extra variables passed to the templates.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, serde_json::Value>,
+}
+
+/// Loads an answers file, picking the format from its extension.
+pub fn load_answers(path: &Path) -> Result<Answers> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read answers file {}: {}", path.display(), e))?;
+
+    let answers = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&content).map_err(|e| {
+            invalid_input(format!("Invalid answers file {}: {}", path.display(), e))
+        })?,
+        Some("toml") => toml::from_str(&content).map_err(|e| {
+            invalid_input(format!("Invalid answers file {}: {}", path.display(), e))
+        })?,
+        _ => {
+            return Err(invalid_input(format!(
+                "Unsupported answers file {}, expected a .json or .toml file",
+                path.display()
+            )))
+        }
+    };
+
+    Ok(answers)
+}
+
+/// Loads a recorded answers file, which has to describe the project
+/// completely so it can be recreated as it was.
+pub fn load_replay(path: &Path) -> Result<Answers> {
+    let answers = load_answers(path)?;
+
+    for (field, missing) in [
+        ("name", answers.name.is_none()),
+        ("package_manager", answers.package_manager.is_none()),
+    ] {
+        if missing {
+            return Err(invalid_input(format!(
+                "Replay file {} is missing `{}`",
+                path.display(),
+                field
+            )));
+        }
+    }
+
+    if let Some(version) = &answers.version {
+        if version != env!("CARGO_PKG_VERSION") {
+            log_warn(&format!(
+                "Replay file {} was recorded with create-v1-app {}, this is {}",
+                path.display(),
+                version,
+                env!("CARGO_PKG_VERSION")
+            ));
+        }
+    }
+
+    Ok(answers)
+}
+
+/// Writes the answers as pretty printed JSON, stamped with the CLI version.
+pub fn save_answers(path: &Path, answers: &Answers) -> Result<()> {
+    let answers = Answers {
+        version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        ..answers.clone()
+    };
+    let content = serde_json::to_string_pretty(&answers)?;
+    fs::write(path, format!("{}\n", content))
+        .map_err(|e| anyhow!("Failed to write answers file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_and_toml_answers() {
+        let json: Answers = serde_json::from_str(
+            r#"{
+                "name": "acme",
+                "services": ["resend", "trigger"],
+                "package_manager": "pnpm",
+                "variables": { "company": "Acme" }
+            }"#,
+        )
+        .unwrap();
+
+        let toml: Answers = toml::from_str(
+            r#"
+            name = "acme"
+            services = ["resend", "trigger"]
+            package_manager = "pnpm"
+
+            [variables]
+            company = "Acme"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(json, toml);
+        assert_eq!(json.services, vec![Service::Resend, Service::Trigger]);
+        assert_eq!(json.variables["company"], "Acme");
+    }
+
+    #[test]
+    fn test_saved_answers_replay() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("answers.json");
+        let answers = Answers {
+            name: Some("acme".to_string()),
+            services: vec![Service::Cal],
+            package_manager: Some("bun".to_string()),
+            ..Default::default()
+        };
+
+        save_answers(&path, &answers).unwrap();
+        let replayed = load_replay(&path).unwrap();
+
+        assert_eq!(replayed.version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(
+            Answers {
+                version: None,
+                ..replayed
+            },
+            answers
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        assert!(serde_json::from_str::<Answers>(r#"{ "nmae": "acme" }"#).is_err());
+    }
+}
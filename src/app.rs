@@ -1,110 +1,61 @@
 use anyhow::Result;
 use console::style;
 use indicatif::HumanDuration;
-use std::{fs, path::Path, time::Instant};
-use tera::Context;
+use std::collections::BTreeMap;
 
 use crate::cli::Service;
-use crate::logger::log_debug;
-use crate::utils::get_templates_path;
-use crate::workspace::{get_workspaces, Workspace};
-use crate::{cleanup, CLEANUP_MANAGER};
-use crate::{
-    logger::log_info,
-    tera::{initialize_tera, TERA},
-    workspace::process_workspace,
-};
-
+use crate::context::RunContext;
+use crate::git::DEFAULT_COMMIT_MESSAGE;
+use crate::logger::log_info;
+use crate::resolver::{confirm_plan, resolve_services};
+use crate::scaffold::{check_project_dir, Scaffold};
+use crate::utils::confirm_package_manager;
+
+#[allow(clippy::too_many_arguments)]
 pub fn create_new_app(
+    ctx: &RunContext,
     name: &str,
     services: &[Service],
     package_manager: Option<&str>,
+    scope: &str,
+    providers: &[String],
+    variables: &BTreeMap<String, serde_json::Value>,
+    commit_message: Option<&str>,
 ) -> Result<()> {
-    let start_time = Instant::now();
-
-    let package_manager = crate::utils::confirm_package_manager(package_manager)?;
-    log_info(&format!("Using package manager: {}", package_manager));
-
-    // init tera
-    initialize_tera()?;
-
-    let tera = TERA.lock().unwrap();
-
-    log_debug(&format!(
-        "Creating new app: {} with {} service(s) by {}",
-        name,
-        services.len(),
-        package_manager
-    ));
-
-    let mut context = Context::new();
-    context.insert("project_name", name);
-    context.insert("package_manager", &package_manager);
-
-    let project_path = Path::new(name);
-    fs::create_dir_all(project_path)?;
-
-    let apps_path = project_path.join("apps");
-    fs::create_dir_all(&apps_path)?;
-
-    let packages_path = project_path.join("packages");
-    fs::create_dir_all(&packages_path)?;
-
-    {
-        let mut manager = CLEANUP_MANAGER.lock().unwrap();
-        manager.add_task(cleanup::CleanupTask::RemoveDirectory(
-            project_path.to_path_buf(),
-        ));
-        manager.add_task(cleanup::CleanupTask::RemoveDirectory(
-            packages_path.to_path_buf(),
-        ));
-        manager.add_task(cleanup::CleanupTask::RemoveDirectory(
-            apps_path.to_path_buf(),
-        ));
+    let plan = resolve_services(services, &[])?;
+    if !plan.is_empty() && !confirm_plan(ctx, &plan)? {
+        return Ok(());
     }
 
-    let templates_root = get_templates_path();
-
-    let mut workspaces = get_workspaces(&templates_root, project_path);
-
-    // if we specify some services, we add them to the workspace
-    if !services.is_empty() {
-        for service in services {
-            let workspace = Workspace {
-                name: service.to_string(),
-                source_path: templates_root.join("services").join(service.to_string()),
-                dest_path: project_path.join("packages").join(service.to_string()),
-                is_root: false,
-            };
-            workspaces.push(workspace);
-        }
-    }
+    let package_manager = confirm_package_manager(&ctx.prompter, package_manager)?;
+    log_info(&format!("Using package manager: {}", package_manager));
 
-    let total_steps = workspaces.len() + 1;
+    let project_path = ctx.current_dir.join(name);
+    check_project_dir(&project_path)?;
 
-    for (step, workspace) in workspaces.iter().enumerate() {
-        log_info(&format!(
-            "[{}/{}] Processing workspace: {}",
-            step + 1,
-            total_steps,
-            workspace.name
-        ));
-        process_workspace(&workspace, &tera, &context, &package_manager)?;
-    }
+    let report = Scaffold::new(name)
+        .services(plan.services())
+        .package_manager(&package_manager)
+        .scope(scope)
+        .providers(providers.to_vec())
+        .variables(variables.clone())
+        .template(ctx.templates_path.clone())
+        .git(commit_message.is_some())
+        .commit_message(commit_message.unwrap_or(DEFAULT_COMMIT_MESSAGE))
+        .generate(&ctx.current_dir)?;
 
-    log_info(&format!(
-        "[{}/{}] Installing dependencies...",
-        total_steps, total_steps
-    ));
+    log_info("Installing dependencies...");
 
     // Install dependencies
     // install_dependencies(project_path, &package_manager)?;
 
-    println!(
-        "{}{}",
-        style("V1 app created successfully! in ").bold().dim(),
-        HumanDuration(start_time.elapsed())
-    );
+    if !ctx.json {
+        println!(
+            "{}{}",
+            style("V1 app created successfully! in ").bold().dim(),
+            HumanDuration(report.duration)
+        );
+    }
 
     Ok(())
 }
@@ -0,0 +1,542 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{invalid_input, Error},
+    lockfile::{
+        read_lockfile, scoped_package_name, update_lockfile, AddedWorkspace, Lockfile,
+        DEFAULT_SCOPE, LOCKFILE_NAME,
+    },
+    logger::{log_debug, log_info, log_warn},
+    package_json::{PackageJson, Workspaces},
+    tera::{load_templates, workspace_context},
+    workspace::{process_workspace, Output, Workspace},
+};
+
+/// What a new workspace is generated as, which decides where it goes.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceKind {
+    #[clap(help = "An application in apps/")]
+    App,
+    #[clap(help = "An internal package in packages/")]
+    Package,
+}
+
+impl WorkspaceKind {
+    /// The directory of the project, and of the blueprints, it goes in.
+    pub fn dir(&self) -> &'static str {
+        match self {
+            WorkspaceKind::App => "apps",
+            WorkspaceKind::Package => "packages",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            WorkspaceKind::App => "app",
+            WorkspaceKind::Package => "package",
+        }
+    }
+}
+
+/// A workspace template in `blueprints/<apps|packages>/<name>`, described by
+/// an optional `<name>.json` next to it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Blueprint {
+    pub name: String,
+    pub kind: WorkspaceKind,
+    pub description: String,
+    /// Build outputs of the workspace's `build` task in the root turbo.json.
+    pub outputs: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlueprintManifest {
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    outputs: Vec<String>,
+}
+
+pub fn blueprints_dir(templates_root: &Path, kind: WorkspaceKind) -> PathBuf {
+    templates_root.join("blueprints").join(kind.dir())
+}
+
+/// Returns the blueprints shipped in the templates for `kind`, sorted by name.
+pub fn available_blueprints(templates_root: &Path, kind: WorkspaceKind) -> Result<Vec<Blueprint>> {
+    let dir = blueprints_dir(templates_root, kind);
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().is_dir())
+                .filter_map(|e| e.file_name().to_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let path = dir.join(format!("{}.json", name));
+            let manifest: BlueprintManifest = match fs::read_to_string(&path) {
+                Ok(content) => {
+                    serde_json::from_str(&content).map_err(|e| Error::TemplateRender {
+                        template: path.display().to_string(),
+                        message: e.to_string(),
+                    })?
+                }
+                Err(_) => BlueprintManifest::default(),
+            };
+            Ok(Blueprint {
+                name,
+                kind,
+                description: manifest.description,
+                outputs: manifest.outputs,
+            })
+        })
+        .collect()
+}
+
+/// Workspace names become directory and npm package names.
+pub fn is_valid_workspace_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(invalid_input(format!(
+            "Invalid workspace name {}, use lowercase letters, numbers and dashes, starting with a letter",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Generates `<apps|packages>/<name>` from a blueprint and registers it in
+/// the root package.json (or pnpm-workspace.yaml), tsconfig.json references
+/// and turbo.json, and in the lockfile so later commands know about it.
+/// Returns the files written or updated.
+pub fn generate_workspace(
+    project_dir: &Path,
+    templates_root: &Path,
+    kind: WorkspaceKind,
+    name: &str,
+    blueprint: &str,
+) -> Result<Vec<PathBuf>> {
+    is_valid_workspace_name(name)?;
+    let relative = format!("{}/{}", kind.dir(), name);
+    let dest_path = project_dir.join(&relative);
+    if dest_path.exists() {
+        return Err(invalid_input(format!(
+            "Workspace {} already exists",
+            relative
+        )));
+    }
+
+    let blueprints = available_blueprints(templates_root, kind)?;
+    if blueprints.is_empty() {
+        let dir = blueprints_dir(templates_root, kind);
+        return Err(Error::TemplateNotFound(dir.display().to_string()).into());
+    }
+    let Some(blueprint) = blueprints.iter().find(|b| b.name == blueprint) else {
+        let names: Vec<&str> = blueprints.iter().map(|b| b.name.as_str()).collect();
+        return Err(invalid_input(format!(
+            "Unknown {} blueprint {}, expected one of: {}",
+            kind.label(),
+            blueprint,
+            names.join(", ")
+        )));
+    };
+
+    let package_json = PackageJson::read(project_dir)?;
+    let (lockfile, has_lockfile) = match read_lockfile(project_dir)? {
+        Some(lockfile) => (lockfile, true),
+        None => (
+            Lockfile::new(
+                package_json.name()?,
+                DEFAULT_SCOPE,
+                package_json.package_manager()?,
+            ),
+            false,
+        ),
+    };
+    let package_name = scoped_package_name(&lockfile.scope, name);
+    let context = workspace_context(&lockfile, name);
+
+    log_info(&format!(
+        "Adding {} {} from the {} blueprint",
+        kind.label(),
+        name,
+        blueprint.name
+    ));
+    let workspace = Workspace {
+        name: name.to_string(),
+        source_path: blueprints_dir(templates_root, kind).join(&blueprint.name),
+        dest_path: dest_path.clone(),
+        is_root: false,
+    };
+    let tera = load_templates(templates_root)?;
+    let mut files = process_workspace(
+        &workspace,
+        &tera,
+        templates_root,
+        &context,
+        &lockfile.package_manager,
+        &mut Output::Disk,
+    )?;
+
+    // whether the blueprint brings a package.json, a package.json.tera or
+    // none, it is named after the workspace
+    let mut workspace_json = if dest_path.join("package.json").is_file() {
+        PackageJson::read(&dest_path)?
+    } else {
+        PackageJson {
+            path: dest_path.join("package.json"),
+            other: serde_json::Map::from_iter([("private".to_string(), json!(true))]),
+            ..Default::default()
+        }
+    };
+    workspace_json.name = Some(package_name.clone());
+    workspace_json.write()?;
+    if !files.contains(&workspace_json.path) {
+        files.push(workspace_json.path.clone());
+    }
+
+    if dest_path.join("tsconfig.json").is_file() {
+        files.extend(add_tsconfig_reference(project_dir, &relative));
+    }
+    files.extend(add_turbo_task(
+        project_dir,
+        &package_name,
+        &blueprint.outputs,
+    ));
+    files.extend(register_workspace(
+        project_dir,
+        package_json,
+        &lockfile.package_manager,
+        &relative,
+    )?);
+
+    if has_lockfile {
+        update_lockfile(project_dir, |lockfile| {
+            lockfile.workspaces.push(AddedWorkspace {
+                kind,
+                name: name.to_string(),
+                blueprint: blueprint.name.clone(),
+            })
+        })?;
+        files.push(project_dir.join(LOCKFILE_NAME));
+    }
+
+    Ok(files)
+}
+
+/// Whether a workspace pattern such as `apps/*` includes `path`.
+fn covers(pattern: &str, path: &str) -> bool {
+    if let Some(base) = pattern.strip_suffix("/**") {
+        return path.starts_with(&format!("{}/", base));
+    }
+    if let Some(base) = pattern.strip_suffix("/*") {
+        return Path::new(path).parent() == Some(Path::new(base));
+    }
+    pattern.trim_start_matches("./") == path
+}
+
+/// Adds the workspace to pnpm-workspace.yaml for pnpm, to the root
+/// package.json otherwise, unless a pattern already includes it.
+fn register_workspace(
+    project_dir: &Path,
+    mut package_json: PackageJson,
+    package_manager: &str,
+    relative: &str,
+) -> Result<Option<PathBuf>> {
+    let pnpm_workspace = project_dir.join("pnpm-workspace.yaml");
+    if package_manager == "pnpm" && pnpm_workspace.is_file() {
+        let content = fs::read_to_string(&pnpm_workspace)?;
+        if pnpm_packages(&content).iter().any(|p| covers(p, relative)) {
+            return Ok(None);
+        }
+        fs::write(&pnpm_workspace, add_pnpm_package(&content, relative))
+            .map_err(|e| anyhow!("Failed to write {}: {}", pnpm_workspace.display(), e))?;
+        log_debug(&format!("Updated {}", pnpm_workspace.display()));
+        return Ok(Some(pnpm_workspace));
+    }
+
+    let workspaces = package_json
+        .workspaces
+        .get_or_insert_with(Workspaces::default)
+        .patterns_mut();
+    if workspaces.iter().any(|p| covers(p, relative)) {
+        return Ok(None);
+    }
+    workspaces.push(relative.to_string());
+    package_json.write()?;
+    Ok(Some(package_json.path))
+}
+
+/// The entries of the `packages` list of pnpm-workspace.yaml.
+fn pnpm_packages(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .skip_while(|line| !line.starts_with("packages:"))
+        .skip(1)
+        .take_while(|line| line.starts_with(' ') || line.trim().is_empty())
+        .filter_map(|line| line.trim().strip_prefix('-'))
+        .map(|item| {
+            item.trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .to_string()
+        })
+        .collect()
+}
+
+fn add_pnpm_package(content: &str, relative: &str) -> String {
+    let item = format!("  - \"{}\"", relative);
+    let mut lines: Vec<&str> = content.lines().collect();
+    let Some(start) = lines.iter().position(|line| line.starts_with("packages:")) else {
+        let separator = if content.is_empty() || content.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        return format!("{}{}packages:\n{}\n", content, separator, item);
+    };
+
+    let mut end = start + 1;
+    while end < lines.len() && lines[end].trim_start().starts_with('-') {
+        end += 1;
+    }
+    lines.insert(end, &item);
+    format!("{}\n", lines.join("\n"))
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn write_json(path: &Path, value: &Value) -> Result<()> {
+    fs::write(path, format!("{}\n", serde_json::to_string_pretty(value)?))
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    log_debug(&format!("Updated {}", path.display()));
+    Ok(())
+}
+
+/// Adds the workspace to the `references` of the root tsconfig.json. A
+/// tsconfig that can't be parsed, e.g. because of comments, is only warned
+/// about.
+fn add_tsconfig_reference(project_dir: &Path, relative: &str) -> Option<PathBuf> {
+    let path = project_dir.join("tsconfig.json");
+    if !path.is_file() {
+        return None;
+    }
+
+    let result = read_json(&path).and_then(|mut tsconfig| {
+        let reference = format!("./{}", relative);
+        let references = tsconfig
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("expected an object"))?
+            .entry("references")
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .ok_or_else(|| anyhow!("`references` is not an array"))?;
+        if references
+            .iter()
+            .any(|r| r["path"].as_str().is_some_and(|p| covers(p, relative)))
+        {
+            return Ok(false);
+        }
+        references.push(json!({ "path": reference }));
+        write_json(&path, &tsconfig)?;
+        Ok(true)
+    });
+
+    match result {
+        Ok(updated) => updated.then_some(path),
+        Err(e) => {
+            log_warn(&format!(
+                "Could not add {} to the references of {}: {}",
+                relative,
+                path.display(),
+                e
+            ));
+            None
+        }
+    }
+}
+
+/// Registers the workspace's own build in the root turbo.json as
+/// `<package>#build`, a copy of the `build` task (in `pipeline` for turbo 1)
+/// with the blueprint's outputs, so turbo caches what this workspace builds.
+fn add_turbo_task(project_dir: &Path, package_name: &str, outputs: &[String]) -> Option<PathBuf> {
+    let path = project_dir.join("turbo.json");
+    if !path.is_file() {
+        return None;
+    }
+
+    let result = read_json(&path).and_then(|mut turbo| {
+        let tasks_key = if turbo.get("pipeline").is_some() {
+            "pipeline"
+        } else {
+            "tasks"
+        };
+        let Some(tasks) = turbo.get_mut(tasks_key).and_then(Value::as_object_mut) else {
+            log_debug("turbo.json has no tasks, leaving it as is");
+            return Ok(false);
+        };
+        let Some(build) = tasks.get("build").and_then(Value::as_object) else {
+            log_debug("turbo.json has no build task, leaving it as is");
+            return Ok(false);
+        };
+        let key = format!("{}#build", package_name);
+        if tasks.contains_key(&key) {
+            return Ok(false);
+        }
+
+        let mut task = build.clone();
+        if !outputs.is_empty() {
+            task.insert("outputs".to_string(), json!(outputs));
+        }
+        tasks.insert(key, Value::Object(task));
+        write_json(&path, &turbo)?;
+        Ok(true)
+    });
+
+    match result {
+        Ok(updated) => updated.then_some(path),
+        Err(e) => {
+            log_warn(&format!(
+                "Could not add the build task of {} to {}: {}",
+                package_name,
+                path.display(),
+                e
+            ));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scaffold::{test_scaffold, test_templates};
+
+    #[test]
+    fn test_generate_app_registers_it() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let templates = test_templates(root);
+        let project = test_scaffold(root, "acme")
+            .package_manager("pnpm")
+            .generate(root)
+            .unwrap()
+            .project_dir;
+        let blueprint = templates.join("blueprints/apps/worker");
+        fs::create_dir_all(blueprint.join("src")).unwrap();
+        fs::write(
+            templates.join("blueprints/apps/worker.json"),
+            r#"{ "description": "A background worker", "outputs": ["dist/**"] }"#,
+        )
+        .unwrap();
+        fs::write(
+            blueprint.join("package.json.tera"),
+            r#"{ "name": "{{ package_name }}", "private": true }"#,
+        )
+        .unwrap();
+        fs::write(blueprint.join("tsconfig.json"), "{}\n").unwrap();
+        fs::write(
+            blueprint.join("src/index.ts.tera"),
+            "console.log(\"{{ package_name }}\");\n",
+        )
+        .unwrap();
+        fs::write(
+            project.join("pnpm-workspace.yaml"),
+            "packages:\n  - \"apps/web\"\n  - \"packages/*\"\n",
+        )
+        .unwrap();
+        fs::write(
+            project.join("tsconfig.json"),
+            r#"{ "files": [], "references": [{ "path": "./apps/web" }] }"#,
+        )
+        .unwrap();
+        fs::write(
+            project.join("turbo.json"),
+            r#"{ "tasks": { "build": { "dependsOn": ["^build"], "outputs": [".next/**"] } } }"#,
+        )
+        .unwrap();
+
+        let files =
+            generate_workspace(&project, &templates, WorkspaceKind::App, "jobs", "worker").unwrap();
+        let again = generate_workspace(&project, &templates, WorkspaceKind::App, "jobs", "worker");
+        let unknown = generate_workspace(&project, &templates, WorkspaceKind::App, "docs", "docs");
+        let package = generate_workspace(
+            &project,
+            &templates,
+            WorkspaceKind::Package,
+            "shared",
+            "worker",
+        );
+
+        let index = fs::read_to_string(project.join("apps/jobs/src/index.ts")).unwrap();
+        let package_json = PackageJson::read(&project.join("apps/jobs")).unwrap();
+        let pnpm_workspace = fs::read_to_string(project.join("pnpm-workspace.yaml")).unwrap();
+        let tsconfig = read_json(&project.join("tsconfig.json")).unwrap();
+        let turbo = read_json(&project.join("turbo.json")).unwrap();
+        let lockfile = read_lockfile(&project).unwrap().unwrap();
+        let workspaces =
+            crate::workspace::get_workspaces(&templates, &project, &lockfile.workspaces);
+
+        assert_eq!(files.len(), 7);
+        assert_eq!(index, "console.log(\"@v1/jobs\");\n");
+        assert_eq!(package_json.name.as_deref(), Some("@v1/jobs"));
+        assert_eq!(package_json.other["private"], true);
+        assert_eq!(
+            pnpm_workspace,
+            "packages:\n  - \"apps/web\"\n  - \"packages/*\"\n  - \"apps/jobs\"\n"
+        );
+        assert_eq!(tsconfig["references"][1]["path"], "./apps/jobs");
+        assert_eq!(turbo["tasks"]["build"]["outputs"], json!([".next/**"]));
+        assert_eq!(
+            turbo["tasks"]["@v1/jobs#build"],
+            json!({ "dependsOn": ["^build"], "outputs": ["dist/**"] })
+        );
+        assert_eq!(
+            lockfile.workspaces,
+            [AddedWorkspace {
+                kind: WorkspaceKind::App,
+                name: "jobs".to_string(),
+                blueprint: "worker".to_string(),
+            }]
+        );
+        let jobs = workspaces.last().unwrap();
+        assert_eq!(
+            (jobs.source_path.clone(), jobs.dest_path.clone()),
+            (blueprint.clone(), project.join("apps/jobs"))
+        );
+        assert!(again.is_err());
+        assert_eq!(crate::error_code(&unknown.unwrap_err()), "E_INVALID_INPUT");
+        assert_eq!(
+            crate::error_code(&package.unwrap_err()),
+            "E_TEMPLATE_NOT_FOUND"
+        );
+    }
+
+    #[test]
+    fn test_workspace_patterns() {
+        assert!(covers("apps/*", "apps/docs"));
+        assert!(covers("./apps/docs", "apps/docs"));
+        assert!(covers("packages/**", "packages/ui"));
+        assert!(!covers("apps/*", "packages/docs"));
+        assert!(!covers("apps/web", "apps/docs"));
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{error::Error, logger::log_debug};
+
+/// Stops a running generation from another thread. The work is checked
+/// between files and workspaces, what was written is rolled back and it
+/// fails with `Error::Cancelled`.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    /// The `cancel_all` calls made before the token was created, the later
+    /// ones cancel it.
+    epoch: usize,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken {
+            cancelled: Arc::default(),
+            epoch: CANCEL_ALL_CALLS.load(Ordering::SeqCst),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || CANCEL_ALL_CALLS.load(Ordering::SeqCst) > self.epoch
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts the times everything running was cancelled, e.g. on ctrl+c. Work
+/// started afterwards isn't affected, so there is nothing to reset.
+static CANCEL_ALL_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static TOKENS: RefCell<Vec<CancellationToken>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Cancels every token created so far.
+pub fn cancel_all() {
+    CANCEL_ALL_CALLS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Whether any token the work on this thread runs with was cancelled.
+pub fn is_cancelled() -> bool {
+    TOKENS.with(|tokens| tokens.borrow().iter().any(CancellationToken::is_cancelled))
+}
+
+/// Checks `token` too while `f` runs on this thread.
+pub fn with_cancellation<T>(token: &CancellationToken, f: impl FnOnce() -> T) -> T {
+    TOKENS.with(|tokens| tokens.borrow_mut().push(token.clone()));
+    let result = f();
+    TOKENS.with(|tokens| tokens.borrow_mut().pop());
+    result
+}
+
+/// Fails with `Error::Cancelled` once the work was cancelled.
+pub fn check_cancelled() -> Result<()> {
+    if is_cancelled() {
+        log_debug("Cancelled, stopping");
+        return Err(Error::Cancelled.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_only_cancels_its_own_work() {
+        let token = CancellationToken::new();
+        let other = CancellationToken::new();
+        token.cancel();
+
+        assert!(with_cancellation(&token, check_cancelled).is_err());
+        assert!(with_cancellation(&other, check_cancelled).is_ok());
+        assert!(with_cancellation(&token, || with_cancellation(&other, check_cancelled)).is_err());
+        assert!(check_cancelled().is_ok());
+    }
+}
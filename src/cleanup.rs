@@ -1,9 +1,14 @@
-use crate::logger;
+use crate::{
+    cli::Service,
+    env, imports,
+    lockfile::{project_scope, scoped_package_name, update_lockfile},
+    logger,
+    package_json::PackageJson,
+    utils::{is_source_file, project_files},
+};
 use anyhow::Result;
-use regex::Regex;
 use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
 
 pub enum CleanupTask {
     RemoveDirectory(PathBuf),
@@ -66,7 +71,7 @@ impl CleanupManager {
     }
 }
 
-fn remove_service(project_dir: &Path, service_name: &str) -> Result<()> {
+pub fn remove_service(project_dir: &Path, service_name: &str) -> Result<()> {
     logger::log_debug(&format!(
         "Removing service {} from {}",
         service_name,
@@ -82,49 +87,87 @@ fn remove_service(project_dir: &Path, service_name: &str) -> Result<()> {
         ));
     }
 
+    let package = scoped_package_name(&project_scope(project_dir)?, service_name);
+
     update_root_package_json(project_dir, service_name)?;
-    remove_service_references(project_dir, service_name)?;
+    remove_package_dependencies(project_dir, &package)?;
+    remove_service_references(project_dir, &package)?;
+
+    if let Ok(service) = service_name.parse::<Service>() {
+        env::remove_service_env(project_dir, &service)?;
+        update_lockfile(project_dir, |lockfile| {
+            lockfile.services.retain(|s| s != &service)
+        })?;
+    }
 
     Ok(())
 }
 
 fn update_root_package_json(project_dir: &Path, service_name: &str) -> Result<()> {
-    let package_json_path = project_dir.join("package.json");
-    let content = fs::read_to_string(&package_json_path)?;
-    let mut package_json: serde_json::Value = serde_json::from_str(&content)?;
+    let mut package_json = PackageJson::read(project_dir)?;
 
-    if let Some(workspaces) = package_json["workspaces"].as_array_mut() {
+    if let Some(workspaces) = package_json.workspaces.as_mut() {
         let service_workspace = format!("packages/{}", service_name);
-        workspaces.retain(|w| w != &service_workspace);
-        let new_content = serde_json::to_string_pretty(&package_json)?;
-        fs::write(package_json_path, new_content)?;
-        logger::log_debug("Updated root package.json");
+        workspaces
+            .patterns_mut()
+            .retain(|w| w != &service_workspace);
+        package_json.write()?;
     }
 
     Ok(())
 }
 
-fn remove_service_references(project_dir: &Path, service_name: &str) -> Result<()> {
-    let import_regex = Regex::new(&format!(
-        r#"(?m)^\s*import.*from ["']@v1/{service_name}["'].*$"#
-    ))?;
-    let use_regex = Regex::new(&format!(r#"(?m)^\s*use.*@v1/{service_name}.*$"#))?;
+/// Drops the package from the dependencies of every package.json in the
+/// project.
+fn remove_package_dependencies(project_dir: &Path, package: &str) -> Result<()> {
+    for entry in project_files(project_dir) {
+        let path = entry.path();
+        if !path.is_file() || entry.file_name() != "package.json" {
+            continue;
+        }
+
+        let mut package_json = PackageJson::read(path.parent().unwrap_or(project_dir))?;
+        if package_json.remove_dependency(package) {
+            package_json.write()?;
+            logger::log_debug(&format!("Removed {} from {}", package, path.display()));
+        }
+    }
 
-    for entry in WalkDir::new(project_dir).into_iter().filter_map(|e| e.ok()) {
+    Ok(())
+}
+
+fn remove_service_references(project_dir: &Path, package: &str) -> Result<()> {
+    for entry in project_files(project_dir) {
         let path = entry.path();
-        if path.is_file()
-            && path.extension().map_or(false, |ext| {
-                ext == "ts" || ext == "tsx" || ext == "js" || ext == "jsx"
-            })
-        {
+        if path.is_file() && is_source_file(path) {
             let content = fs::read_to_string(path)?;
-            let new_content = import_regex.replace_all(&content, "").to_string();
-            let new_content = use_regex.replace_all(&new_content, "").to_string();
+            if !content.contains(package) {
+                continue;
+            }
 
-            if new_content != content {
-                fs::write(path, new_content)?;
+            let removal = match imports::remove_package_imports(path, &content, package) {
+                Ok(removal) => removal,
+                Err(e) => {
+                    logger::log_warn(&format!("Skipping {}: {}", path.display(), e));
+                    continue;
+                }
+            };
+
+            if removal.removed > 0 {
+                fs::write(path, &removal.source)?;
                 logger::log_debug(&format!("Updated file: {}", path.display()));
             }
+
+            for reference in &removal.dangling {
+                logger::log_warn(&format!(
+                    "{}:{}:{}: `{}` still refers to {}, please remove it by hand",
+                    path.display(),
+                    reference.line,
+                    reference.column,
+                    reference.name,
+                    package
+                ));
+            }
         }
     }
 
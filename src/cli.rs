@@ -1,16 +1,35 @@
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::Result;
 use clap::{Args, Parser, Subcommand, ValueEnum};
-use dialoguer::theme::ColorfulTheme;
-use dialoguer::Input;
+use clap_verbosity_flag::{InfoLevel, Verbosity};
+use serde::{Deserialize, Serialize};
 
+use crate::answers::{load_answers, load_replay, save_answers, Answers};
 use crate::app::create_new_app;
-use crate::logger::log_debug;
-use crate::service::add_services;
-use crate::utils::{get_package_json, get_templates_path, is_valid_project_name, PackageJson};
-use crate::workspace::get_workspaces;
+use crate::blueprint::{available_blueprints, WorkspaceKind};
+use crate::config::{load_config, run_config_get, run_config_list, run_config_set, Config};
+use crate::context::RunContext;
+use crate::diff::run_diff;
+use crate::doctor::run_doctor;
+use crate::env::{prompt_service_env, run_env_check};
+use crate::error::{invalid_input, Error};
+use crate::events::{with_json_output, OutputFormat};
+use crate::git::DEFAULT_COMMIT_MESSAGE;
+use crate::list::{available_providers, run_list, ListTarget};
+use crate::lockfile::DEFAULT_SCOPE;
+use crate::logger::{log_debug, log_info, set_log_level};
+use crate::project::{add_services_with, add_workspace};
+use crate::prompt::Prompter;
+use crate::rename::run_rename;
+use crate::resolver::confirm_plan;
+use crate::service::{installed_services, remove_services};
+use crate::utils::{
+    bundled_templates_path, find_project_root, is_valid_project_name, is_valid_scope,
+};
 use crate::{service::select_services, utils::select_package_manager};
 
 #[derive(Parser)]
@@ -19,6 +38,52 @@ use crate::{service::select_services, utils::select_package_manager};
 pub struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    #[command(flatten)]
+    verbose: Verbosity<InfoLevel>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        help = "The format of what is printed to stdout"
+    )]
+    output: OutputFormat,
+
+    #[arg(
+        short = 'y',
+        long = "yes",
+        visible_alias = "non-interactive",
+        global = true,
+        help = "Never prompt, take the default answer or fail when there is none"
+    )]
+    yes: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        help = "A .json or .toml file supplying every answer up front"
+    )]
+    answers: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        conflicts_with = "answers",
+        help = "Recreate a project from an answers file recorded by an interactive session"
+    )]
+    replay: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Run as if started in DIR instead of the current directory"
+    )]
+    cwd: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -26,25 +91,99 @@ enum Commands {
     #[command(about = "Create a new V1 app")]
     New {
         #[arg(help = "The name of the new project")]
-        name: String,
+        name: Option<String>,
 
         #[arg(short, long, help = "A list of services to add to the project")]
         services: Option<Vec<Service>>,
 
         #[arg(short, long, help = "The package manager to use for the project")]
         package_manager: Option<String>,
+
+        #[arg(long, help = "The npm scope of the generated packages, defaults to v1")]
+        scope: Option<String>,
+
+        #[arg(long, help = "Don't initialize a git repository")]
+        no_git: bool,
+
+        #[arg(
+            long,
+            value_name = "MESSAGE",
+            conflicts_with = "no_git",
+            help = "The message of the initial commit"
+        )]
+        commit_message: Option<String>,
     },
-    #[command(about = "Add a service or provider to an existing V1 app")]
+    #[command(about = "Add a service, provider, app or package to an existing V1 app")]
     Add {
         #[command(subcommand)]
         subcommand: AddSubcommands,
     },
+    #[command(about = "List the available services and providers")]
+    List {
+        #[arg(value_enum, default_value_t, help = "What to list")]
+        target: ListTarget,
+
+        #[arg(long, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Manage the environment variables of an existing V1 app")]
+    Env {
+        #[command(subcommand)]
+        subcommand: EnvSubcommands,
+    },
+    #[command(about = "Read and write the create-v1-app configuration")]
+    Config {
+        #[command(subcommand)]
+        subcommand: ConfigSubcommands,
+    },
+    #[command(about = "Remove a service from an existing V1 app")]
+    Remove {
+        #[command(subcommand)]
+        subcommand: RemoveSubcommands,
+    },
+    #[command(about = "Check the health of an existing V1 app")]
+    Doctor {
+        #[arg(long, help = "Apply the fixes that can't lose anything")]
+        fix: bool,
+
+        #[arg(long, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Rename an existing V1 app or move its packages to another npm scope")]
+    Rename {
+        #[arg(help = "The new name of the project")]
+        name: Option<String>,
+
+        #[arg(long, help = "The new npm scope of the packages")]
+        scope: Option<String>,
+
+        #[arg(long, help = "Only show the changes, without applying them")]
+        dry_run: bool,
+    },
+    #[command(about = "Show how an existing V1 app differs from its templates")]
+    Diff {
+        #[arg(help = "Only compare one workspace or service, by name or path")]
+        path: Option<String>,
+
+        #[arg(
+            long,
+            help = "Compare even when the project was generated by another version"
+        )]
+        force: bool,
+
+        #[arg(long, help = "Print the result as JSON")]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum AddSubcommands {
     #[command(about = "Add a service to an existing V1 app")]
     Services(ServicesArgs),
+    #[command(about = "Add an app generated from a blueprint to an existing V1 app")]
+    App(WorkspaceArgs),
+    #[command(about = "Add an internal package generated from a blueprint to an existing V1 app")]
+    Package(WorkspaceArgs),
     #[command(about = "Add a provider to an existing V1 app")]
     Provider {
         #[arg(help = "The name of the provider to add")]
@@ -52,13 +191,76 @@ enum AddSubcommands {
     },
 }
 
+#[derive(Subcommand)]
+enum RemoveSubcommands {
+    #[command(about = "Remove services from an existing V1 app")]
+    Services {
+        #[arg(help = "The names of the services to remove", value_delimiter = ',')]
+        services: Vec<Service>,
+    },
+}
+
+#[derive(Subcommand)]
+enum EnvSubcommands {
+    #[command(about = "Check the environment variables required by the installed services")]
+    Check {
+        #[arg(long, help = "Print the result as JSON")]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigSubcommands {
+    #[command(about = "Print the value of a config key")]
+    Get {
+        #[arg(help = "The config key, e.g. package_manager")]
+        key: String,
+    },
+    #[command(about = "Set a config key")]
+    Set {
+        #[arg(help = "The config key, e.g. package_manager")]
+        key: String,
+
+        #[arg(help = "The new value, comma separated for services")]
+        value: String,
+
+        #[arg(
+            long,
+            help = "Write to .v1apprc in the current directory instead of the user config"
+        )]
+        local: bool,
+    },
+    #[command(about = "Print every config value that is set")]
+    List,
+}
+
 #[derive(Args)]
 pub struct ServicesArgs {
     #[arg(help = "The names of the services to add", value_delimiter = ',')]
     services: Vec<Service>,
+
+    #[arg(
+        long,
+        help = "Prompt for the services' environment variables and write them to .env.local"
+    )]
+    env: bool,
 }
 
-#[derive(ValueEnum, Clone, Debug)]
+#[derive(Args)]
+pub struct WorkspaceArgs {
+    #[arg(help = "The name of the new workspace, e.g. docs")]
+    name: String,
+
+    #[arg(
+        short,
+        long,
+        help = "The blueprint to generate it from, see `list blueprints`"
+    )]
+    blueprint: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Service {
     #[clap(help = "Calendar service for scheduling")]
     Cal,
@@ -93,9 +295,9 @@ impl FromStr for Service {
     }
 }
 
-impl ToString for Service {
-    fn to_string(&self) -> String {
-        match self {
+impl std::fmt::Display for Service {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
             Service::Cal => "cal",
             Service::Dub => "dub",
             Service::Openpanel => "openpanel",
@@ -103,82 +305,437 @@ impl ToString for Service {
             Service::Trigger => "trigger",
             Service::Sentry => "sentry",
             Service::Upstash => "upstash",
-        }
-        .to_string()
+        };
+        write!(f, "{}", name)
     }
 }
 
-pub fn parse_cli(args: Vec<String>) -> Result<()> {
+impl Service {
+    pub fn description(&self) -> String {
+        self.to_possible_value()
+            .and_then(|v| v.get_help().map(|help| help.to_string()))
+            .unwrap_or_default()
+    }
+}
+
+/// Runs the command of `args`, printing its events to `events` with
+/// `--output json`.
+pub fn parse_cli(args: Vec<String>, events: Box<dyn Write>) -> Result<()> {
     if args.is_empty() {
-        return run_interactive_dialogue();
+        let ctx = RunContext {
+            current_dir: std::env::current_dir()?,
+            templates_path: bundled_templates_path(),
+            prompter: Prompter::default(),
+            json: false,
+        };
+        return run_interactive_dialogue(&ctx, &Config::default());
     }
 
-    let cli = Cli::parse_from(args);
+    // clap would exit the process, which may be a Node host
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(e) if !e.use_stderr() => {
+            // --help and --version
+            e.print()?;
+            return Ok(());
+        }
+        Err(e) => {
+            let message = e.to_string();
+            return Err(invalid_input(
+                message.trim_start_matches("error: ").trim_end(),
+            ));
+        }
+    };
+
+    set_log_level(cli.verbose.log_level_filter());
+
+    // the process keeps its directory, a Node host shares it
+    let current_dir = match &cli.cwd {
+        Some(dir) if !dir.is_dir() => {
+            return Err(invalid_input(format!(
+                "Cannot run in {}, it is not a directory",
+                dir.display()
+            )));
+        }
+        Some(dir) => std::path::absolute(dir)?,
+        None => std::env::current_dir()?,
+    };
+    let config = load_config(&current_dir)?;
+    let ctx = RunContext {
+        templates_path: config
+            .template
+            .clone()
+            .unwrap_or_else(bundled_templates_path),
+        prompter: Prompter::new(cli.yes || cli.answers.is_some() || cli.replay.is_some()),
+        json: cli.output == OutputFormat::Json,
+        current_dir,
+    };
 
-    match cli.command {
+    let answers = match (&cli.answers, &cli.replay) {
+        (Some(path), _) => load_answers(&ctx.current_dir.join(path))?,
+        (_, Some(path)) => load_replay(&ctx.current_dir.join(path))?,
+        _ => Answers::default(),
+    };
+
+    // commands generating files end their event stream with a summary
+    let generates = matches!(
+        cli.command,
+        None | Some(Commands::New { .. } | Commands::Add { .. } | Commands::Remove { .. })
+    );
+    let has_answers = cli.answers.is_some() || cli.replay.is_some();
+
+    let run = || run_command(&ctx, cli.command, answers, has_answers, &config);
+    if ctx.json {
+        with_json_output(events, generates, run)
+    } else {
+        run()
+    }
+}
+
+fn run_command(
+    ctx: &RunContext,
+    command: Option<Commands>,
+    answers: Answers,
+    has_answers: bool,
+    config: &Config,
+) -> Result<()> {
+    let current_dir = &ctx.current_dir;
+    match command {
         Some(Commands::New {
             name,
             services,
             package_manager,
+            scope,
+            no_git,
+            commit_message,
         }) => {
-            is_valid_project_name(&name)?;
-
-            let services = services.unwrap_or_default();
-
-            let package_manager = package_manager.unwrap_or("npm".to_string());
-
-            create_new_app(&name, &services, Some(&package_manager))
+            let answers = Answers {
+                name: name.or(answers.name),
+                services: services.unwrap_or(answers.services),
+                package_manager: package_manager.or(answers.package_manager),
+                scope: scope.or(answers.scope),
+                ..answers
+            };
+            let git = git_commit_message(no_git, commit_message, config);
+            create_from_answers(ctx, answers, config, git)
         }
         Some(Commands::Add { subcommand }) => match subcommand {
             AddSubcommands::Services(services) => {
                 log_debug(&format!("Adding services: {}", services.services.len()));
-                let templates_root = get_templates_path();
-
-                let PackageJson {
-                    name,
-                    package_manager: _,
-                } = get_package_json(None)?;
-                let project_dir = Path::new(&name);
-                let mut workspaces = get_workspaces(&templates_root, project_dir);
-                add_services(&mut workspaces, &services.services)?;
-                Ok(())
+                let project_dir = find_project_root(current_dir)?;
+
+                run_add_services(ctx, &project_dir, &services.services, services.env)
+            }
+            AddSubcommands::App(args) => run_add_workspace(ctx, WorkspaceKind::App, args),
+            AddSubcommands::Package(args) => run_add_workspace(ctx, WorkspaceKind::Package, args),
+            AddSubcommands::Provider { .. } => {
+                Err(invalid_input("Adding providers is not supported yet"))
+            }
+        },
+        Some(Commands::List { target, json }) => {
+            // only the installed services need a project to list from
+            let project_dir = match target {
+                ListTarget::Installed => find_project_root(current_dir)?,
+                _ => find_project_root(current_dir).unwrap_or(current_dir.clone()),
+            };
+            run_list(&project_dir, &ctx.templates_path, target, json || ctx.json)
+        }
+        Some(Commands::Env { subcommand }) => match subcommand {
+            EnvSubcommands::Check { json } => {
+                let project_dir = find_project_root(current_dir)?;
+                let services = installed_services(&project_dir);
+                run_env_check(&project_dir, &services, json || ctx.json)
+            }
+        },
+        Some(Commands::Config { subcommand }) => match subcommand {
+            ConfigSubcommands::Get { key } => run_config_get(current_dir, &key),
+            ConfigSubcommands::Set { key, value, local } => {
+                run_config_set(current_dir, &key, &value, local)
+            }
+            ConfigSubcommands::List => run_config_list(current_dir),
+        },
+        Some(Commands::Remove { subcommand }) => match subcommand {
+            RemoveSubcommands::Services { services } => {
+                remove_services(&find_project_root(current_dir)?, &services)
             }
-            _ => unreachable!(),
         },
-        None => run_interactive_dialogue(),
+        Some(Commands::Doctor { fix, json }) => {
+            run_doctor(&find_project_root(current_dir)?, fix, json || ctx.json)
+        }
+        Some(Commands::Rename {
+            name,
+            scope,
+            dry_run,
+        }) => run_rename(
+            &ctx.prompter,
+            &find_project_root(current_dir)?,
+            name.as_deref(),
+            scope.as_deref(),
+            dry_run,
+        ),
+        Some(Commands::Diff { path, force, json }) => {
+            // a path names the workspace relative to the working directory
+            let filter = path.map(|path| match current_dir.join(&path) {
+                dir if dir.exists() => dir.display().to_string(),
+                _ => path,
+            });
+            run_diff(
+                &find_project_root(current_dir)?,
+                &ctx.templates_path,
+                filter.as_deref(),
+                force,
+                json || ctx.json,
+            )
+        }
+        None if has_answers => {
+            let git = git_commit_message(false, None, config);
+            create_from_answers(ctx, answers, config, git)
+        }
+        None => run_interactive_dialogue(ctx, config),
+    }
+}
+
+/// The message of the initial commit, or none when git is turned off by
+/// `--no-git` or the `git` config.
+fn git_commit_message(no_git: bool, message: Option<String>, config: &Config) -> Option<String> {
+    if no_git || config.git == Some(false) {
+        return None;
     }
+    Some(message.unwrap_or(DEFAULT_COMMIT_MESSAGE.to_string()))
 }
 
-fn run_interactive_dialogue() -> Result<()> {
-    let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("What would you like to do?")
-        .items(&["Create a new v1 app", "Add a service to an existing v1 app"])
-        .default(0)
-        .interact()
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+fn create_from_answers(
+    ctx: &RunContext,
+    answers: Answers,
+    config: &Config,
+    commit_message: Option<String>,
+) -> Result<()> {
+    let name = answers.name.ok_or_else(|| {
+        Error::MissingAnswer(
+            "A project name is required, pass it as an argument or in an answers file".to_string(),
+        )
+    })?;
+    is_valid_project_name(&name)?;
+
+    if !answers.providers.is_empty() {
+        let providers = available_providers(&ctx.templates_path)?;
+        for provider in &answers.providers {
+            if !providers.contains(provider) {
+                return Err(invalid_input(format!("Unknown provider: {}", provider)));
+            }
+        }
+    }
+
+    let package_manager = answers
+        .package_manager
+        .or(config.package_manager.clone())
+        .unwrap_or("npm".to_string());
+
+    let services = if answers.services.is_empty() {
+        config.services.clone().unwrap_or_default()
+    } else {
+        answers.services
+    };
+
+    let scope = answers
+        .scope
+        .or(config.scope.clone())
+        .unwrap_or(DEFAULT_SCOPE.to_string());
+    let scope = scope.trim_start_matches('@');
+    is_valid_scope(scope)?;
+
+    create_new_app(
+        ctx,
+        &name,
+        &services,
+        Some(&package_manager),
+        scope,
+        &answers.providers,
+        &answers.variables,
+        commit_message.as_deref(),
+    )
+}
+
+/// Offers to save the answers of an interactive session for `--replay`.
+fn record_answers(ctx: &RunContext, answers: Answers) -> Result<()> {
+    let path = ctx.current_dir.join(format!(
+        "{}.answers.json",
+        answers.name.as_deref().unwrap_or("v1-app")
+    ));
+    let save = ctx.prompter.confirm(
+        &format!(
+            "Save your answers to {} to replay this setup?",
+            path.display()
+        ),
+        true,
+    )?;
+
+    if save {
+        save_answers(&path, &answers)?;
+        println!(
+            "Answers saved, recreate the project with: create-v1-app --replay {}",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn run_add_services(
+    ctx: &RunContext,
+    project_dir: &Path,
+    services: &[Service],
+    prompt_env: bool,
+) -> Result<()> {
+    let added = add_services_with(project_dir, &ctx.templates_path, services, |plan| {
+        confirm_plan(ctx, plan)
+    })?;
+    if prompt_env {
+        for service in &added {
+            prompt_service_env(&ctx.prompter, project_dir, service)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_add_workspace(ctx: &RunContext, kind: WorkspaceKind, args: WorkspaceArgs) -> Result<()> {
+    let project_dir = find_project_root(&ctx.current_dir)?;
+    let blueprint = match args.blueprint {
+        Some(blueprint) => blueprint,
+        None => {
+            let blueprints = available_blueprints(&ctx.templates_path, kind)?;
+            let items: Vec<String> = blueprints
+                .iter()
+                .map(|b| match b.description.as_str() {
+                    "" => b.name.clone(),
+                    description => format!("{}: {}", b.name, description),
+                })
+                .collect();
+            if items.is_empty() {
+                return Err(invalid_input(format!(
+                    "The templates have no {} blueprints",
+                    kind.label()
+                )));
+            }
+            let selection = ctx.prompter.select("Select a blueprint", &items, 0)?;
+            blueprints[selection].name.clone()
+        }
+    };
+
+    let workspace_dir = add_workspace(
+        &project_dir,
+        &ctx.templates_path,
+        kind,
+        &args.name,
+        &blueprint,
+    )?;
+    log_info(&format!(
+        "Added {}, install the dependencies to link it",
+        workspace_dir
+            .strip_prefix(&project_dir)
+            .unwrap_or(&workspace_dir)
+            .display()
+    ));
+    Ok(())
+}
+
+fn run_interactive_dialogue(ctx: &RunContext, config: &Config) -> Result<()> {
+    let prompter = &ctx.prompter;
+    if !prompter.is_interactive() {
+        return Err(Error::MissingAnswer(
+            "No command given, pass a subcommand or --answers when running non-interactively"
+                .to_string(),
+        )
+        .into());
+    }
+
+    let selection = prompter.select(
+        "What would you like to do?",
+        &["Create a new v1 app", "Add a service to an existing v1 app"],
+        0,
+    )?;
 
     match selection {
         0 => {
             // create new app
-            let name: String = Input::with_theme(&ColorfulTheme::default())
-                .with_prompt("Enter the project name")
-                .default("myapp".into())
-                .validate_with(|input: &String| is_valid_project_name(input))
-                .interact_text()
-                .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            let name = prompter.input("Enter the project name", Some("myapp"), false, |input| {
+                is_valid_project_name(input)
+            })?;
 
-            let services = select_services()?;
-            let package_manager = select_package_manager()?;
+            let services = select_services(
+                prompter,
+                config.services.as_deref().unwrap_or_default(),
+                &[],
+            )?;
+            let package_manager =
+                select_package_manager(prompter, config.package_manager.as_deref())?;
 
-            create_new_app(&name, &services, Some(&package_manager))
+            let scope = prompter.input(
+                "Enter the npm scope of the packages",
+                Some(config.scope.as_deref().unwrap_or(DEFAULT_SCOPE)),
+                false,
+                is_valid_scope,
+            )?;
+
+            create_new_app(
+                ctx,
+                &name,
+                &services,
+                Some(&package_manager),
+                &scope,
+                &[],
+                &BTreeMap::new(),
+                git_commit_message(false, None, config).as_deref(),
+            )?;
+
+            record_answers(
+                ctx,
+                Answers {
+                    name: Some(name),
+                    services,
+                    package_manager: Some(package_manager),
+                    scope: Some(scope),
+                    ..Default::default()
+                },
+            )
         }
         1 => {
             // add services to existing app
-            let _services = select_services()?;
-            let _templates_root = Path::new("templates");
-            //add_services(&services, &templates_root)?;
-            Ok(())
+            let project_dir = find_project_root(&ctx.current_dir)?;
+
+            let services = select_services(prompter, &[], &installed_services(&project_dir))?;
+            if services.is_empty() {
+                return Ok(());
+            }
+            // asked once here, not again for every service
+            let prompt_env = services.iter().any(|s| !s.env_vars().is_empty())
+                && prompter.confirm("Fill in the environment variables now?", false)?;
+
+            run_add_services(ctx, &project_dir, &services, prompt_env)
         }
         _ => unreachable!(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::LevelFilter;
+
+    #[test]
+    fn test_verbosity_flags() {
+        let level = |flags: &[&str]| {
+            let args = std::iter::once("create-v1-app").chain(flags.iter().copied());
+            Cli::try_parse_from(args)
+                .unwrap()
+                .verbose
+                .log_level_filter()
+        };
+
+        assert_eq!(level(&[]), LevelFilter::Info);
+        assert_eq!(level(&["-v"]), LevelFilter::Debug);
+        assert_eq!(level(&["-vv"]), LevelFilter::Trace);
+        assert_eq!(level(&["-q"]), LevelFilter::Warn);
+        assert_eq!(level(&["-qq"]), LevelFilter::Error);
+        assert_eq!(level(&["list", "--quiet"]), LevelFilter::Warn);
+    }
+}
@@ -0,0 +1,270 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{cli::Service, error::invalid_input, logger::log_debug};
+
+pub const RC_FILE_NAME: &str = ".v1apprc";
+
+/// Defaults loaded from the user config and `.v1apprc` files. Every value is
+/// optional, CLI flags take precedence over all of them.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Package manager used when none is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package_manager: Option<String>,
+    /// Services preselected for new projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub services: Option<Vec<Service>>,
+    /// Directory to load the templates from instead of the bundled ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<PathBuf>,
+    /// npm scope of the generated packages, without the `@`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Whether to initialize a git repository in new projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<bool>,
+}
+
+pub const CONFIG_KEYS: [&str; 5] = ["package_manager", "services", "template", "scope", "git"];
+
+impl Config {
+    /// Overlays the values set in `other` on top of this config.
+    fn merge(self, other: Config) -> Config {
+        Config {
+            package_manager: other.package_manager.or(self.package_manager),
+            services: other.services.or(self.services),
+            template: other.template.or(self.template),
+            scope: other.scope.or(self.scope),
+            git: other.git.or(self.git),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let value = match key {
+            "package_manager" => self.package_manager.clone(),
+            "services" => self.services.as_ref().map(|services| {
+                services
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }),
+            "template" => self.template.as_ref().map(|t| t.display().to_string()),
+            "scope" => self.scope.clone(),
+            "git" => self.git.map(|git| git.to_string()),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(value)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "package_manager" => self.package_manager = Some(value.to_string()),
+            "services" => {
+                let services = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.parse::<Service>().map_err(invalid_input))
+                    .collect::<Result<Vec<_>>>()?;
+                self.services = Some(services);
+            }
+            "template" => self.template = Some(PathBuf::from(value)),
+            "scope" => self.scope = Some(value.trim_start_matches('@').to_string()),
+            "git" => {
+                self.git = Some(value.parse().map_err(|_| {
+                    invalid_input(format!(
+                        "Invalid value for git: {}, expected true or false",
+                        value
+                    ))
+                })?)
+            }
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+}
+
+fn unknown_key(key: &str) -> anyhow::Error {
+    invalid_input(format!(
+        "Unknown config key: {}, expected one of {}",
+        key,
+        CONFIG_KEYS.join(", ")
+    ))
+}
+
+/// `~/.config/create-v1-app/config.toml`, honouring `XDG_CONFIG_HOME`.
+pub fn user_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join("create-v1-app").join("config.toml"))
+}
+
+/// Every `.v1apprc` from the filesystem root down to `dir`.
+pub fn rc_paths(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = dir
+        .ancestors()
+        .map(|ancestor| ancestor.join(RC_FILE_NAME))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.reverse();
+    paths
+}
+
+/// Reads the config at `path`, a relative `template` being resolved against
+/// the directory of the file.
+pub fn read_config(path: &Path) -> Result<Config> {
+    let mut config = read_config_file(path)?;
+    if let (Some(template), Some(dir)) = (&config.template, path.parent()) {
+        config.template = Some(dir.join(template));
+    }
+    Ok(config)
+}
+
+fn read_config_file(path: &Path) -> Result<Config> {
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read config {}: {}", path.display(), e))?;
+    toml::from_str(&content)
+        .map_err(|e| invalid_input(format!("Invalid config {}: {}", path.display(), e)))
+}
+
+pub fn write_config(path: &Path, config: &Config) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, toml::to_string_pretty(config)?)
+        .map_err(|e| anyhow!("Failed to write config {}: {}", path.display(), e))
+}
+
+/// The files making up the config for `dir`, lowest precedence first.
+pub fn config_sources(dir: &Path) -> Vec<PathBuf> {
+    user_config_path()
+        .into_iter()
+        .chain(rc_paths(dir))
+        .collect()
+}
+
+/// Loads the user config overlaid with every `.v1apprc` up to `dir`, nearer
+/// files winning.
+pub fn load_config(dir: &Path) -> Result<Config> {
+    let mut config = Config::default();
+    for path in config_sources(dir) {
+        log_debug(&format!("Loading config from {}", path.display()));
+        config = config.merge(read_config(&path)?);
+    }
+    Ok(config)
+}
+
+pub fn run_config_get(dir: &Path, key: &str) -> Result<()> {
+    if let Some(value) = load_config(dir)?.get(key)? {
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+pub fn run_config_set(dir: &Path, key: &str, value: &str, local: bool) -> Result<()> {
+    let path = if local {
+        dir.join(RC_FILE_NAME)
+    } else {
+        user_config_path().ok_or_else(|| anyhow!("Could not find the home directory"))?
+    };
+
+    // kept as written, relative values are resolved when the file is read
+    let mut config = read_config_file(&path)?;
+    match key {
+        // the user config isn't next to what a relative path was meant from
+        "template" if !local => config.set(key, &dir.join(value).display().to_string())?,
+        _ => config.set(key, value)?,
+    }
+    write_config(&path, &config)?;
+    println!("Set {} in {}", key, path.display());
+    Ok(())
+}
+
+pub fn run_config_list(dir: &Path) -> Result<()> {
+    let config = load_config(dir)?;
+    for key in CONFIG_KEYS {
+        if let Some(value) = config.get(key)? {
+            println!("{} = {}", key, value);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearer_config_wins() {
+        let user: Config = toml::from_str(
+            r#"
+            package_manager = "pnpm"
+            services = ["resend"]
+            git = true
+            "#,
+        )
+        .unwrap();
+        let rc: Config = toml::from_str(r#"package_manager = "bun""#).unwrap();
+
+        let config = user.merge(rc);
+        assert_eq!(config.package_manager.as_deref(), Some("bun"));
+        assert_eq!(config.services, Some(vec![Service::Resend]));
+        assert_eq!(config.git, Some(true));
+    }
+
+    #[test]
+    fn test_get_and_set() {
+        let mut config = Config::default();
+        config.set("services", "cal, dub").unwrap();
+        config.set("scope", "@acme").unwrap();
+        config.set("git", "false").unwrap();
+
+        assert_eq!(config.get("services").unwrap().as_deref(), Some("cal,dub"));
+        assert_eq!(config.get("scope").unwrap().as_deref(), Some("acme"));
+        assert_eq!(config.get("git").unwrap().as_deref(), Some("false"));
+        assert_eq!(config.get("template").unwrap(), None);
+
+        assert!(config.set("git", "maybe").is_err());
+        assert!(config.set("services", "nope").is_err());
+        assert!(config.get("colour").is_err());
+    }
+
+    #[test]
+    fn test_rc_files_are_found_in_parents() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let nested = root.join("apps").join("web");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            root.join(RC_FILE_NAME),
+            "scope = \"acme\"\ntemplate = \"templates\"\n",
+        )
+        .unwrap();
+        fs::write(nested.join(RC_FILE_NAME), "git = false\n").unwrap();
+
+        let paths = rc_paths(&nested);
+        let config = paths.iter().fold(Config::default(), |config, path| {
+            config.merge(read_config(path).unwrap())
+        });
+
+        assert_eq!(
+            paths.last().map(|p| p.as_path()),
+            Some(nested.join(RC_FILE_NAME).as_path())
+        );
+        assert_eq!(config.scope.as_deref(), Some("acme"));
+        assert_eq!(config.template, Some(root.join("templates")));
+        assert_eq!(config.git, Some(false));
+    }
+}
@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use crate::prompt::Prompter;
+
+/// The settings of one run of the CLI, read from its flags and config and
+/// handed down to the commands. Runs sharing a process, like the ones of a
+/// Node host, each get their own.
+pub struct RunContext {
+    /// The directory of `--cwd`, the process's otherwise.
+    pub current_dir: PathBuf,
+    /// The templates of the `template` config, the bundled ones otherwise.
+    pub templates_path: PathBuf,
+    pub prompter: Prompter,
+    /// Whether the events are printed as JSON, with `--output json`.
+    pub json: bool,
+}
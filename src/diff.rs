@@ -0,0 +1,340 @@
+use anyhow::Result;
+use console::style;
+use serde::Serialize;
+use similar::TextDiff;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    env::{app_dir, merge_service_env, service_apps},
+    error::invalid_input,
+    lockfile::{read_lockfile, LOCKFILE_NAME},
+    logger::{log_debug, log_warn},
+    service::service_workspace,
+    tera::{load_templates, template_context, workspace_context},
+    workspace::{get_workspaces, process_workspace, Output, Workspace},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftStatus {
+    /// The project's file differs from the rendered template.
+    Modified,
+    /// The template renders a file the project doesn't have.
+    Missing,
+}
+
+/// A generated file that no longer matches its template.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDrift {
+    /// Relative to the project root.
+    pub path: PathBuf,
+    pub status: DriftStatus,
+    /// Unified diff from the template to the project.
+    pub diff: String,
+}
+
+/// Re-renders the project's templates in memory with the context recorded
+/// in its lockfile and compares them with the files in the project.
+/// `filter` limits it to one workspace or service, by name or path. Files
+/// the templates don't render are left out. A project generated by another
+/// version is only compared when `force` is set, as changes to the
+/// templates would show up as local edits.
+pub fn project_drift(
+    project_dir: &Path,
+    templates_root: &Path,
+    filter: Option<&str>,
+    force: bool,
+) -> Result<Vec<FileDrift>> {
+    let lockfile = read_lockfile(project_dir)?.ok_or_else(|| {
+        invalid_input(format!(
+            "{} has no {}, the context it was generated with is unknown",
+            project_dir.display(),
+            LOCKFILE_NAME
+        ))
+    })?;
+    if lockfile.version != env!("CARGO_PKG_VERSION") {
+        if !force {
+            return Err(invalid_input(format!(
+                "The project was generated with create-v1-app {}, the templates of {} may have changed since, pass --force to compare anyway",
+                lockfile.version,
+                env!("CARGO_PKG_VERSION")
+            )));
+        }
+        log_warn(&format!(
+            "The project was generated with create-v1-app {}, comparing with the templates of {}",
+            lockfile.version,
+            env!("CARGO_PKG_VERSION")
+        ));
+    }
+
+    let mut workspaces = get_workspaces(templates_root, project_dir, &lockfile.workspaces);
+    for service in &lockfile.services {
+        workspaces.push(service_workspace(project_dir, templates_root, service));
+    }
+    let workspaces = select_workspaces(workspaces, project_dir, filter)?;
+
+    let tera = load_templates(templates_root)?;
+    let context = template_context(&lockfile);
+    let mut output = Output::Memory(BTreeMap::new());
+    for workspace in &workspaces {
+        // the added workspaces were rendered with their own names too
+        let added = lockfile
+            .workspaces
+            .iter()
+            .find(|added| project_dir.join(added.path()) == workspace.dest_path);
+        process_workspace(
+            workspace,
+            &tera,
+            templates_root,
+            &match added {
+                Some(added) => workspace_context(&lockfile, &added.name),
+                None => context.clone(),
+            },
+            &lockfile.package_manager,
+            &mut output,
+        )?;
+    }
+    let Output::Memory(mut rendered) = output else {
+        unreachable!()
+    };
+
+    // services add their variables to the apps' .env.example once rendered
+    for service in &lockfile.services {
+        for app in service_apps(service) {
+            let dir = app_dir(project_dir, app);
+            let path = dir.join(".env.example");
+            if !dir.exists() || !workspaces.iter().any(|w| contains(w, &path)) {
+                continue;
+            }
+            let content = rendered
+                .get(&path)
+                .map(|content| String::from_utf8_lossy(content).to_string())
+                .unwrap_or_default();
+            rendered.insert(path, merge_service_env(&content, service, app).into_bytes());
+        }
+    }
+
+    let mut drift = Vec::new();
+    for (path, template) in rendered {
+        let relative = path
+            .strip_prefix(project_dir)
+            .unwrap_or(&path)
+            .to_path_buf();
+        let (status, current) = match fs::read(&path) {
+            Ok(current) if current == template => continue,
+            Ok(current) => (DriftStatus::Modified, Some(current)),
+            Err(_) => (DriftStatus::Missing, None),
+        };
+        log_debug(&format!("{} differs from the template", relative.display()));
+        drift.push(FileDrift {
+            diff: unified_diff(&relative, &template, current.as_deref()),
+            path: relative,
+            status,
+        });
+    }
+    Ok(drift)
+}
+
+/// Whether `path` is one of the files `workspace` generates.
+fn contains(workspace: &Workspace, path: &Path) -> bool {
+    if workspace.is_root {
+        path.parent() == Some(workspace.dest_path.as_path())
+    } else {
+        path.starts_with(&workspace.dest_path)
+    }
+}
+
+fn select_workspaces(
+    workspaces: Vec<Workspace>,
+    project_dir: &Path,
+    filter: Option<&str>,
+) -> Result<Vec<Workspace>> {
+    let Some(filter) = filter else {
+        return Ok(workspaces);
+    };
+
+    let canonical = fs::canonicalize(filter).ok();
+    let selected: Vec<Workspace> = workspaces
+        .iter()
+        .filter(|w| {
+            w.name == filter
+                || w.dest_path == project_dir.join(filter)
+                || canonical.is_some() && canonical == fs::canonicalize(&w.dest_path).ok()
+        })
+        .cloned()
+        .collect();
+
+    if selected.is_empty() {
+        let names: Vec<&str> = workspaces.iter().map(|w| w.name.as_str()).collect();
+        return Err(invalid_input(format!(
+            "Unknown workspace or service {}, expected one of: {}",
+            filter,
+            names.join(", ")
+        )));
+    }
+    Ok(selected)
+}
+
+/// A unified diff from `template` to `current`, `None` when it is missing.
+pub fn unified_diff(path: &Path, template: &[u8], current: Option<&[u8]>) -> String {
+    let old_header = format!("a/{}", path.display());
+    let new_header = match current {
+        Some(_) => format!("b/{}", path.display()),
+        None => "/dev/null".to_string(),
+    };
+
+    let (Ok(template), Ok(current)) = (
+        std::str::from_utf8(template),
+        std::str::from_utf8(current.unwrap_or_default()),
+    ) else {
+        return format!("Binary files {} and {} differ\n", old_header, new_header);
+    };
+
+    TextDiff::from_lines(template, current)
+        .unified_diff()
+        .header(&old_header, &new_header)
+        .to_string()
+}
+
+pub fn print_diff(diff: &str) {
+    for line in diff.lines() {
+        let line = if line.starts_with("---") || line.starts_with("+++") {
+            style(line).bold()
+        } else if line.starts_with("@@") {
+            style(line).cyan()
+        } else if line.starts_with('+') {
+            style(line).green()
+        } else if line.starts_with('-') {
+            style(line).red()
+        } else {
+            style(line)
+        };
+        println!("{}", line);
+    }
+}
+
+/// Prints how the project differs from its templates.
+pub fn run_diff(
+    project_dir: &Path,
+    templates_root: &Path,
+    filter: Option<&str>,
+    force: bool,
+    json: bool,
+) -> Result<()> {
+    let drift = project_drift(project_dir, templates_root, filter, force)?;
+
+    if json {
+        let report = serde_json::json!({ "files": drift });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if drift.is_empty() {
+        println!("{}", style("No changes from the template.").green().bold());
+        return Ok(());
+    }
+    for file in &drift {
+        print_diff(&file.diff);
+    }
+    println!(
+        "{}",
+        style(format!("{} file(s) differ from the template", drift.len())).bold()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::Service,
+        lockfile::update_lockfile,
+        scaffold::{test_templates, Scaffold},
+    };
+
+    #[test]
+    fn test_drift_from_rendered_templates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let templates = test_templates(root);
+        for dir in ["services/resend", "blueprints/apps/worker/src"] {
+            fs::create_dir_all(templates.join(dir)).unwrap();
+        }
+        fs::write(
+            templates.join("apps/web/src/site.ts.tera"),
+            "export const name = \"{{ project_name }}\";\nexport const beta = true;\n",
+        )
+        .unwrap();
+        fs::write(
+            templates.join("blueprints/apps/worker/src/index.ts.tera"),
+            "export const name = \"{{ package_name }}\";\n",
+        )
+        .unwrap();
+        fs::write(
+            templates.join("services/resend/package.json.tera"),
+            r#"{ "name": "@{{ scope }}/resend" }"#,
+        )
+        .unwrap();
+
+        let project = Scaffold::new("acme")
+            .services([Service::Resend])
+            .template(&templates)
+            .generate(root)
+            .unwrap()
+            .project_dir;
+        let untouched = project_drift(&project, &templates, None, false).unwrap();
+
+        fs::remove_file(project.join("README.md")).unwrap();
+        fs::write(
+            project.join("apps/web/src/site.ts"),
+            "export const name = \"acme\";\nexport const beta = false;\n",
+        )
+        .unwrap();
+        let drift = project_drift(&project, &templates, None, false).unwrap();
+        let web = project_drift(&project, &templates, Some("apps/web"), false).unwrap();
+        let resend = project_drift(&project, &templates, Some("resend"), false).unwrap();
+        let unknown = project_drift(&project, &templates, Some("docs"), false);
+        update_lockfile(&project, |lockfile| lockfile.version = "0.0.1".to_string()).unwrap();
+        let outdated = project_drift(&project, &templates, None, false);
+        let forced = project_drift(&project, &templates, None, true).unwrap();
+        crate::blueprint::generate_workspace(
+            &project,
+            &templates,
+            crate::blueprint::WorkspaceKind::App,
+            "jobs",
+            "worker",
+        )
+        .unwrap();
+        fs::write(project.join("apps/jobs/src/index.ts"), "export {};\n").unwrap();
+        let jobs = project_drift(&project, &templates, Some("apps/jobs"), true).unwrap();
+
+        assert!(untouched.is_empty());
+        let paths: Vec<(&Path, &DriftStatus)> = drift
+            .iter()
+            .map(|f| (f.path.as_path(), &f.status))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                (Path::new("README.md"), &DriftStatus::Missing),
+                (Path::new("apps/web/src/site.ts"), &DriftStatus::Modified),
+            ]
+        );
+        assert!(drift[1]
+            .diff
+            .contains("-export const beta = true;\n+export const beta = false;\n"));
+        assert_eq!(web.len(), 1);
+        assert!(resend.is_empty());
+        assert!(unknown.is_err());
+        assert!(outdated.unwrap_err().to_string().contains("--force"));
+        assert_eq!(forced.len(), drift.len());
+        assert_eq!(jobs.len(), 1);
+        assert!(jobs[0]
+            .diff
+            .contains("-export const name = \"@v1/jobs\";\n+export {};\n"));
+    }
+}
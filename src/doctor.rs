@@ -0,0 +1,505 @@
+use anyhow::Result;
+use console::style;
+use serde::Serialize;
+use std::{collections::BTreeSet, fs, path::Path};
+
+use crate::{
+    cli::Service,
+    env::{add_service_env, app_dir, parse_env, service_apps},
+    error::Error,
+    imports::module_specifiers,
+    lockfile::{read_lockfile, update_lockfile, Lockfile, DEFAULT_SCOPE, LOCKFILE_NAME},
+    logger::log_debug,
+    package_json::{PackageJson, PACKAGE_MANAGERS},
+    service::installed_services,
+    utils::{is_source_file, project_files},
+};
+
+/// The lock files each package manager writes.
+const PACKAGE_MANAGER_LOCKFILES: [(&str, &str); 5] = [
+    ("npm", "package-lock.json"),
+    ("yarn", "yarn.lock"),
+    ("pnpm", "pnpm-lock.yaml"),
+    ("bun", "bun.lockb"),
+    ("bun", "bun.lock"),
+];
+
+/// A change `--fix` can make without losing anything.
+#[derive(Debug, Clone, PartialEq)]
+enum Fix {
+    RemoveWorkspace(String),
+    RecordService(Service),
+    SetPackageManager(String),
+    AddServiceEnv(Service),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    /// The check that found it, e.g. `workspaces` or `env`.
+    pub check: &'static str,
+    pub message: String,
+    pub suggestion: String,
+    pub fixable: bool,
+    #[serde(skip)]
+    fix: Option<Fix>,
+}
+
+impl Problem {
+    fn new(check: &'static str, message: String, suggestion: String) -> Self {
+        Problem {
+            check,
+            message,
+            suggestion,
+            fixable: false,
+            fix: None,
+        }
+    }
+
+    fn with_fix(mut self, fix: Fix) -> Self {
+        self.fixable = true;
+        self.fix = Some(fix);
+        self
+    }
+}
+
+/// Runs every check against the project, in the order they are reported.
+pub fn diagnose(project_dir: &Path) -> Result<Vec<Problem>> {
+    let package_json = PackageJson::read(project_dir)?;
+    let lockfile = read_lockfile(project_dir)?;
+
+    let mut problems = check_workspaces(project_dir, &package_json);
+    problems.extend(check_imports(project_dir, lockfile.as_ref()));
+    problems.extend(check_services(project_dir, lockfile.as_ref()));
+    problems.extend(check_package_manager(
+        project_dir,
+        &package_json,
+        lockfile.as_ref(),
+    ));
+    problems.extend(check_env(project_dir)?);
+    Ok(problems)
+}
+
+/// Every workspace of the root package.json has to exist.
+fn check_workspaces(project_dir: &Path, package_json: &PackageJson) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for pattern in package_json.workspaces.iter().flat_map(|w| w.patterns()) {
+        if let Some((base, _)) = pattern.split_once('*') {
+            let base = base.trim_end_matches('/');
+            if !project_dir.join(base).is_dir() {
+                problems.push(Problem::new(
+                    "workspaces",
+                    format!("Workspace pattern `{}` matches no directory", pattern),
+                    format!("Create {} or remove the pattern from package.json", base),
+                ));
+            }
+        } else if !project_dir.join(pattern).join("package.json").is_file() {
+            problems.push(
+                Problem::new(
+                    "workspaces",
+                    format!("Workspace `{}` does not exist", pattern),
+                    "Remove it from the workspaces of package.json".to_string(),
+                )
+                .with_fix(Fix::RemoveWorkspace(pattern.clone())),
+            );
+        }
+    }
+    problems
+}
+
+/// Every import of a package of the project's scope has to name a package of
+/// the project.
+fn check_imports(project_dir: &Path, lockfile: Option<&Lockfile>) -> Vec<Problem> {
+    let scope = lockfile.map_or(DEFAULT_SCOPE, |lockfile| lockfile.scope.as_str());
+    let prefix = format!("@{}/", scope);
+
+    let mut packages = BTreeSet::new();
+    let mut sources = Vec::new();
+    for entry in project_files(project_dir) {
+        let path = entry.path();
+        if entry.file_name() == "package.json" {
+            if let Ok(package_json) = PackageJson::read(path.parent().unwrap_or(project_dir)) {
+                packages.extend(package_json.name);
+            }
+        } else if path.is_file() && is_source_file(path) {
+            sources.push(path.to_path_buf());
+        }
+    }
+
+    let mut problems = Vec::new();
+    for path in sources {
+        let Ok(source) = fs::read_to_string(&path) else {
+            continue;
+        };
+        if !source.contains(&prefix) {
+            continue;
+        }
+        let specifiers = match module_specifiers(&path, &source) {
+            Ok(specifiers) => specifiers,
+            Err(e) => {
+                log_debug(&format!("Skipping {}: {}", path.display(), e));
+                continue;
+            }
+        };
+
+        for specifier in specifiers {
+            let Some(name) = specifier.value.strip_prefix(&prefix) else {
+                continue;
+            };
+            let name = name.split('/').next().unwrap_or_default();
+            let package = format!("{}{}", prefix, name);
+            if packages.contains(&package) {
+                continue;
+            }
+
+            let suggestion = match name.parse::<Service>() {
+                Ok(service) => format!("Run `create-v1-app add services {}`", service),
+                Err(_) => format!("Remove the import or add a {} package", package),
+            };
+            problems.push(Problem::new(
+                "imports",
+                format!(
+                    "{}:{} imports {}, which is not a package of the project",
+                    path.strip_prefix(project_dir).unwrap_or(&path).display(),
+                    specifier.line,
+                    package
+                ),
+                suggestion,
+            ));
+        }
+    }
+    problems
+}
+
+/// The services in the lockfile and the service packages have to match.
+fn check_services(project_dir: &Path, lockfile: Option<&Lockfile>) -> Vec<Problem> {
+    let Some(lockfile) = lockfile else {
+        return vec![Problem::new(
+            "services",
+            format!("{} is missing", LOCKFILE_NAME),
+            format!(
+                "Without it commands assume the `{}` scope, recreate the project to get one",
+                DEFAULT_SCOPE
+            ),
+        )];
+    };
+
+    let installed = installed_services(project_dir);
+    let mut problems = Vec::new();
+    for service in &lockfile.services {
+        if !installed.contains(service) {
+            problems.push(Problem::new(
+                "services",
+                format!(
+                    "Service {} is in {} but packages/{} is missing",
+                    service, LOCKFILE_NAME, service
+                ),
+                format!(
+                    "Run `create-v1-app add services {}` to generate it again or remove it from {}",
+                    service, LOCKFILE_NAME
+                ),
+            ));
+        }
+    }
+    for service in &installed {
+        if !lockfile.services.contains(service) {
+            problems.push(
+                Problem::new(
+                    "services",
+                    format!(
+                        "Service {} is installed but not in {}",
+                        service, LOCKFILE_NAME
+                    ),
+                    format!("Add {} to the services of {}", service, LOCKFILE_NAME),
+                )
+                .with_fix(Fix::RecordService(service.clone())),
+            );
+        }
+    }
+    problems
+}
+
+/// package.json, the lockfile and the lock files on disk have to agree on
+/// the package manager.
+fn check_package_manager(
+    project_dir: &Path,
+    package_json: &PackageJson,
+    lockfile: Option<&Lockfile>,
+) -> Vec<Problem> {
+    let package_manager = match package_json.package_manager() {
+        Ok(package_manager) => package_manager,
+        Err(e) => {
+            return vec![Problem::new(
+                "package_manager",
+                e.to_string(),
+                format!(
+                    "Set `packageManager` in package.json to one of {}",
+                    PACKAGE_MANAGERS.join(", ")
+                ),
+            )]
+        }
+    };
+
+    let mut problems = Vec::new();
+    if let Some(lockfile) = lockfile {
+        if lockfile.package_manager != package_manager {
+            problems.push(
+                Problem::new(
+                    "package_manager",
+                    format!(
+                        "{} records {} but package.json uses {}",
+                        LOCKFILE_NAME, lockfile.package_manager, package_manager
+                    ),
+                    format!("Record {} in {}", package_manager, LOCKFILE_NAME),
+                )
+                .with_fix(Fix::SetPackageManager(package_manager.to_string())),
+            );
+        }
+    }
+
+    for (owner, file) in PACKAGE_MANAGER_LOCKFILES {
+        if owner != package_manager && project_dir.join(file).exists() {
+            problems.push(Problem::new(
+                "package_manager",
+                format!(
+                    "Found {} of {} but the project uses {}",
+                    file, owner, package_manager
+                ),
+                format!("Delete {} and run `{} install`", file, package_manager),
+            ));
+        }
+    }
+    problems
+}
+
+/// Every required variable of an installed service has to be declared in
+/// the `.env.example` of the apps using it.
+fn check_env(project_dir: &Path) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    for service in installed_services(project_dir) {
+        for app in service_apps(&service) {
+            let dir = app_dir(project_dir, app);
+            if !dir.exists() {
+                continue;
+            }
+
+            let example = dir.join(".env.example");
+            let declared = if example.exists() {
+                parse_env(&fs::read_to_string(&example)?)
+            } else {
+                Default::default()
+            };
+            for var in service.env_vars() {
+                if var.required && var.apps.contains(&app) && !declared.contains_key(var.name) {
+                    problems.push(
+                        Problem::new(
+                            "env",
+                            format!(
+                                "{} of {} is not declared in apps/{}/.env.example",
+                                var.name, service, app
+                            ),
+                            format!("Declare the environment variables of {}", service),
+                        )
+                        .with_fix(Fix::AddServiceEnv(service.clone())),
+                    );
+                }
+            }
+        }
+    }
+    Ok(problems)
+}
+
+fn apply_fix(project_dir: &Path, fix: &Fix) -> Result<()> {
+    match fix {
+        Fix::RemoveWorkspace(pattern) => {
+            let mut package_json = PackageJson::read(project_dir)?;
+            if let Some(workspaces) = package_json.workspaces.as_mut() {
+                workspaces
+                    .patterns_mut()
+                    .retain(|workspace| workspace != pattern);
+            }
+            package_json.write()
+        }
+        Fix::RecordService(service) => update_lockfile(project_dir, |lockfile| {
+            if !lockfile.services.contains(service) {
+                lockfile.services.push(service.clone());
+            }
+        }),
+        Fix::SetPackageManager(package_manager) => update_lockfile(project_dir, |lockfile| {
+            lockfile.package_manager = package_manager.clone();
+        }),
+        Fix::AddServiceEnv(service) => add_service_env(project_dir, service).map(|_| ()),
+    }
+}
+
+/// Applies the safe fixes, returning the problems they fixed.
+fn fix_problems(project_dir: &Path, problems: &[Problem]) -> Result<Vec<Problem>> {
+    let mut applied: Vec<&Fix> = Vec::new();
+    let mut fixed = Vec::new();
+    for problem in problems {
+        let Some(fix) = &problem.fix else {
+            continue;
+        };
+        // one fix can solve several problems, e.g. every variable of a service
+        if !applied.contains(&fix) {
+            apply_fix(project_dir, fix)?;
+            applied.push(fix);
+        }
+        fixed.push(problem.clone());
+    }
+    Ok(fixed)
+}
+
+fn print_problem(problem: &Problem) {
+    println!(
+        "{} {} {}",
+        style("✗").red().bold(),
+        style(format!("[{}]", problem.check)).dim(),
+        problem.message
+    );
+    let fixable = if problem.fixable {
+        style(" (fixable with --fix)").dim().to_string()
+    } else {
+        String::new()
+    };
+    println!(
+        "    {} {}{}",
+        style("→").cyan(),
+        problem.suggestion,
+        fixable
+    );
+}
+
+/// Checks the project's health, applying the safe fixes with `fix`. Fails
+/// when problems remain.
+pub fn run_doctor(project_dir: &Path, fix: bool, json: bool) -> Result<()> {
+    let mut problems = diagnose(project_dir)?;
+
+    let mut fixed = Vec::new();
+    if fix {
+        fixed = fix_problems(project_dir, &problems)?;
+        if !fixed.is_empty() {
+            problems = diagnose(project_dir)?;
+        }
+    }
+
+    if json {
+        let report = serde_json::json!({
+            "ok": problems.is_empty(),
+            "fixed": fixed,
+            "problems": problems,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for problem in &fixed {
+            println!("{} {}", style("✓ Fixed:").green().bold(), problem.message);
+        }
+        if problems.is_empty() {
+            println!("{}", style("No problems found.").green().bold());
+        }
+        for problem in &problems {
+            print_problem(problem);
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::DoctorFailed(problems.len()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scaffold::test_scaffold;
+
+    #[test]
+    fn test_fix_solves_safe_problems_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = test_scaffold(tmp.path(), "acme")
+            .generate(tmp.path())
+            .unwrap();
+        let dir = project.project_dir.as_path();
+        fs::create_dir_all(dir.join("apps/web/src")).unwrap();
+        fs::create_dir_all(dir.join("apps/app")).unwrap();
+        fs::create_dir_all(dir.join("packages/resend")).unwrap();
+        fs::write(
+            dir.join("package.json"),
+            r#"{ "name": "acme", "packageManager": "pnpm@9.0.0", "workspaces": ["apps/*", "packages/*", "packages/gone"] }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("apps/web/package.json"),
+            r#"{ "name": "@v1/web" }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("packages/resend/package.json"),
+            r#"{ "name": "@v1/resend" }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("apps/web/src/page.tsx"),
+            "import { send } from \"@v1/resend\";\nimport { cal } from \"@v1/cal/client\";\n",
+        )
+        .unwrap();
+
+        let problems = diagnose(dir).unwrap();
+        let result = run_doctor(dir, true, false);
+        let remaining = diagnose(dir).unwrap();
+        let lockfile = read_lockfile(dir).unwrap().unwrap();
+
+        let checks: Vec<&str> = problems.iter().map(|p| p.check).collect();
+        assert_eq!(
+            checks,
+            [
+                "workspaces",
+                "imports",
+                "services",
+                "package_manager",
+                "env"
+            ]
+        );
+        assert!(problems[1]
+            .message
+            .starts_with("apps/web/src/page.tsx:2 imports @v1/cal"));
+        assert_eq!(error_code_of(result), "E_DOCTOR_FAILED");
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].check, "imports");
+        assert_eq!(lockfile.services, [Service::Resend]);
+        assert_eq!(lockfile.package_manager, "pnpm");
+    }
+
+    #[test]
+    fn test_imports_use_the_project_scope() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = test_scaffold(tmp.path(), "acme")
+            .scope("acme")
+            .generate(tmp.path())
+            .unwrap();
+        let dir = project.project_dir.as_path();
+        fs::create_dir_all(dir.join("apps/web/src")).unwrap();
+        fs::create_dir_all(dir.join("packages/ui")).unwrap();
+        fs::write(
+            dir.join("packages/ui/package.json"),
+            r#"{ "name": "@acme/ui" }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("apps/web/src/page.tsx"),
+            "import { Button } from \"@acme/ui\";\nimport { cal } from \"@acme/cal\";\nimport { log } from \"@v1/logger\";\n",
+        )
+        .unwrap();
+
+        let problems = check_imports(dir, read_lockfile(dir).unwrap().as_ref());
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0]
+            .message
+            .starts_with("apps/web/src/page.tsx:2 imports @acme/cal"));
+    }
+
+    fn error_code_of(result: Result<()>) -> &'static str {
+        crate::error::error_code(&result.unwrap_err())
+    }
+}
@@ -0,0 +1,672 @@
+use anyhow::Result;
+use console::style;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+
+use crate::{cli::Service, error::Error, logger::log_debug, prompt::Prompter, utils::print_table};
+
+/// An environment variable a service needs in one or more apps.
+pub struct EnvVar {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub required: bool,
+    pub apps: &'static [&'static str],
+    /// Regex a non-empty value has to match.
+    pub pattern: Option<&'static str>,
+}
+
+impl EnvVar {
+    const fn required(
+        name: &'static str,
+        description: &'static str,
+        apps: &'static [&'static str],
+    ) -> Self {
+        EnvVar {
+            name,
+            description,
+            required: true,
+            apps,
+            pattern: None,
+        }
+    }
+
+    const fn optional(
+        name: &'static str,
+        description: &'static str,
+        apps: &'static [&'static str],
+    ) -> Self {
+        EnvVar {
+            name,
+            description,
+            required: false,
+            apps,
+            pattern: None,
+        }
+    }
+
+    const fn matching(mut self, pattern: &'static str) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+}
+
+const CAL_ENV: &[EnvVar] = &[EnvVar::required(
+    "NEXT_PUBLIC_CAL_LINK",
+    "Cal.com booking link, e.g. your-team/demo",
+    &["web"],
+)];
+
+const DUB_ENV: &[EnvVar] =
+    &[EnvVar::required("DUB_API_KEY", "Dub workspace API key", &["app"]).matching("^dub_")];
+
+const OPENPANEL_ENV: &[EnvVar] = &[
+    EnvVar::required(
+        "NEXT_PUBLIC_OPENPANEL_CLIENT_ID",
+        "OpenPanel client id",
+        &["web", "app"],
+    ),
+    EnvVar::optional(
+        "OPENPANEL_SECRET_KEY",
+        "OpenPanel secret for server side events",
+        &["web", "app"],
+    ),
+];
+
+const RESEND_ENV: &[EnvVar] = &[
+    EnvVar::required("RESEND_API_KEY", "Resend API key", &["app", "api"]).matching("^re_"),
+    EnvVar::optional(
+        "RESEND_AUDIENCE_ID",
+        "Resend audience used for newsletter signups",
+        &["web"],
+    ),
+];
+
+const TRIGGER_ENV: &[EnvVar] = &[
+    EnvVar::required("TRIGGER_PROJECT_ID", "Trigger.dev project id", &["app"]).matching("^proj_"),
+    EnvVar::required("TRIGGER_SECRET_KEY", "Trigger.dev secret key", &["app"]).matching("^tr_"),
+];
+
+const SENTRY_ENV: &[EnvVar] = &[
+    EnvVar::required("NEXT_PUBLIC_SENTRY_DSN", "Sentry DSN", &["web", "app"])
+        .matching(r"^https://[^@\s]+@[^/\s]+/\d+$"),
+    EnvVar::optional(
+        "SENTRY_AUTH_TOKEN",
+        "Sentry auth token used to upload source maps",
+        &["web", "app"],
+    ),
+    EnvVar::optional("SENTRY_ORG", "Sentry organization slug", &["web", "app"]),
+    EnvVar::optional("SENTRY_PROJECT", "Sentry project slug", &["web", "app"]),
+];
+
+const UPSTASH_ENV: &[EnvVar] = &[
+    EnvVar::required("UPSTASH_REDIS_REST_URL", "Upstash Redis REST url", &["app"])
+        .matching(r"^https://\S+$"),
+    EnvVar::required(
+        "UPSTASH_REDIS_REST_TOKEN",
+        "Upstash Redis REST token",
+        &["app"],
+    ),
+];
+
+impl Service {
+    pub fn env_vars(&self) -> &'static [EnvVar] {
+        match self {
+            Service::Cal => CAL_ENV,
+            Service::Dub => DUB_ENV,
+            Service::Openpanel => OPENPANEL_ENV,
+            Service::Resend => RESEND_ENV,
+            Service::Trigger => TRIGGER_ENV,
+            Service::Sentry => SENTRY_ENV,
+            Service::Upstash => UPSTASH_ENV,
+        }
+    }
+
+    fn env_vars_for_app(&self, app: &str) -> Vec<&'static EnvVar> {
+        self.env_vars()
+            .iter()
+            .filter(|var| var.apps.contains(&app))
+            .collect()
+    }
+}
+
+/// Returns the apps a service declares environment variables for.
+pub fn service_apps(service: &Service) -> Vec<&'static str> {
+    let mut apps: Vec<&'static str> = service
+        .env_vars()
+        .iter()
+        .flat_map(|var| var.apps.iter().copied())
+        .collect();
+    apps.sort();
+    apps.dedup();
+    apps
+}
+
+pub fn app_dir(project_dir: &Path, app: &str) -> PathBuf {
+    project_dir.join("apps").join(app)
+}
+
+/// Merges the service's environment variables into `.env.example` of every
+/// app it is used in.
+/// Returns the `.env.example` files that changed.
+pub fn add_service_env(project_dir: &Path, service: &Service) -> Result<Vec<PathBuf>> {
+    let mut updated = Vec::new();
+    for app in service_apps(service) {
+        let dir = app_dir(project_dir, app);
+        if !dir.exists() {
+            log_debug(&format!(
+                "Skipping env for {}: app {} does not exist",
+                service, app
+            ));
+            continue;
+        }
+
+        let path = dir.join(".env.example");
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        let merged = merge_service_env(&content, service, app);
+        if merged != content {
+            fs::write(&path, merged)?;
+            log_debug(&format!("Updated {}", path.display()));
+            updated.push(path);
+        }
+    }
+    Ok(updated)
+}
+
+/// `.env.example` of `app` with the service's variables added, the way
+/// `add_service_env` writes it.
+pub fn merge_service_env(content: &str, service: &Service, app: &str) -> String {
+    merge_env_block(content, service, &service.env_vars_for_app(app))
+}
+
+/// Removes the service's environment variables from `.env.example` and
+/// `.env.local` of every app it is used in.
+pub fn remove_service_env(project_dir: &Path, service: &Service) -> Result<()> {
+    for app in service_apps(service) {
+        let names: Vec<&str> = service
+            .env_vars_for_app(app)
+            .iter()
+            .map(|var| var.name)
+            .collect();
+
+        for file in [".env.example", ".env.local"] {
+            let path = app_dir(project_dir, app).join(file);
+            if !path.exists() {
+                continue;
+            }
+            let content = fs::read_to_string(&path)?;
+            let stripped = strip_env_block(&content, service, &names);
+            if stripped != content {
+                fs::write(&path, stripped)?;
+                log_debug(&format!("Updated {}", path.display()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prompts for the service's environment variables and writes the answers
+/// into `.env.local` of every app it is used in. Whether to fill them in at
+/// all is up to the caller, `--env` or a single question for every service.
+pub fn prompt_service_env(
+    prompter: &Prompter,
+    project_dir: &Path,
+    service: &Service,
+) -> Result<()> {
+    fill_service_env(project_dir, service, |var| {
+        prompter.input(
+            &format!("{} ({})", var.name, var.description),
+            None,
+            !var.required,
+            |_| Ok(()),
+        )
+    })
+}
+
+/// Writes the value `ask` gives for each of the service's environment
+/// variables into `.env.local` of its apps. Only the variables missing from
+/// at least one of those files are asked for, the ones set are left alone.
+fn fill_service_env(
+    project_dir: &Path,
+    service: &Service,
+    mut ask: impl FnMut(&EnvVar) -> Result<String>,
+) -> Result<()> {
+    let mut files = Vec::new();
+    for app in service_apps(service) {
+        let dir = app_dir(project_dir, app);
+        if dir.exists() {
+            let path = dir.join(".env.local");
+            let content = fs::read_to_string(&path).unwrap_or_default();
+            files.push((app, path, content));
+        }
+    }
+    let missing = |app: &str, var: &EnvVar, content: &str| {
+        var.apps.contains(&app) && !parse_env(content).contains_key(var.name)
+    };
+
+    let mut values: BTreeMap<&str, String> = BTreeMap::new();
+    for var in service.env_vars() {
+        if files
+            .iter()
+            .any(|(app, _, content)| missing(app, var, content))
+        {
+            values.insert(var.name, ask(var)?);
+        }
+    }
+
+    for (app, path, mut content) in files {
+        let vars: Vec<(&str, &str)> = service
+            .env_vars()
+            .iter()
+            .filter(|var| missing(app, var, &content))
+            .filter_map(|var| values.get(var.name).map(|v| (var.name, v.as_str())))
+            .collect();
+
+        if vars.is_empty() {
+            continue;
+        }
+
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        for (name, value) in vars {
+            content.push_str(&format!("{}={}\n", name, value));
+        }
+        fs::write(&path, content)?;
+        log_debug(&format!("Updated {}", path.display()));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvProblem {
+    Missing,
+    Empty,
+    Malformed,
+}
+
+impl EnvProblem {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnvProblem::Missing => "missing",
+            EnvProblem::Empty => "empty",
+            EnvProblem::Malformed => "malformed",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct EnvIssue {
+    pub app: String,
+    pub service: Service,
+    pub name: &'static str,
+    pub problem: EnvProblem,
+}
+
+/// Loads an app's env files the way Next.js layers them, `.env.local`
+/// overriding `.env`. The process environment isn't included.
+pub fn load_app_env(app_dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut env = BTreeMap::new();
+    for file in [".env", ".env.local"] {
+        let path = app_dir.join(file);
+        if path.exists() {
+            env.extend(parse_env(&fs::read_to_string(&path)?));
+        }
+    }
+    Ok(env)
+}
+
+/// Checks the installed services' environment variables for every app in
+/// the project. Variables set in the process environment override the env
+/// files.
+pub fn check_project_env(project_dir: &Path, services: &[Service]) -> Result<Vec<EnvIssue>> {
+    let mut issues = Vec::new();
+    let mut apps: Vec<&str> = services.iter().flat_map(service_apps).collect();
+    apps.sort();
+    apps.dedup();
+
+    for app in apps {
+        let dir = app_dir(project_dir, app);
+        if !dir.exists() {
+            continue;
+        }
+
+        let mut env = load_app_env(&dir)?;
+        for service in services {
+            for var in service.env_vars_for_app(app) {
+                if let Ok(value) = std::env::var(var.name) {
+                    env.insert(var.name.to_string(), value);
+                }
+            }
+        }
+
+        for service in services {
+            issues.extend(check_app_env(app, service, &env)?);
+        }
+    }
+
+    Ok(issues)
+}
+
+fn check_app_env(
+    app: &str,
+    service: &Service,
+    env: &BTreeMap<String, String>,
+) -> Result<Vec<EnvIssue>> {
+    let mut issues = Vec::new();
+    for var in service.env_vars_for_app(app) {
+        let problem = match env.get(var.name).map(|v| v.trim()) {
+            None if var.required => Some(EnvProblem::Missing),
+            Some("") if var.required => Some(EnvProblem::Empty),
+            None | Some("") => None,
+            Some(value) => match var.pattern {
+                Some(pattern) if !Regex::new(pattern)?.is_match(value) => {
+                    Some(EnvProblem::Malformed)
+                }
+                _ => None,
+            },
+        };
+
+        if let Some(problem) = problem {
+            issues.push(EnvIssue {
+                app: app.to_string(),
+                service: service.clone(),
+                name: var.name,
+                problem,
+            });
+        }
+    }
+    Ok(issues)
+}
+
+/// Prints the result of [`check_project_env`] as a table or JSON and fails
+/// when any problem was found.
+pub fn run_env_check(project_dir: &Path, services: &[Service], json: bool) -> Result<()> {
+    let issues = check_project_env(project_dir, services)?;
+
+    if json {
+        let report = serde_json::json!({
+            "services": services.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            "ok": issues.is_empty(),
+            "issues": issues
+                .iter()
+                .map(|issue| {
+                    serde_json::json!({
+                        "app": issue.app,
+                        "service": issue.service.to_string(),
+                        "name": issue.name,
+                        "problem": issue.problem.as_str(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if issues.is_empty() {
+        println!(
+            "{}",
+            style("All environment variables are set.").green().bold()
+        );
+    } else {
+        let rows: Vec<[String; 4]> = issues
+            .iter()
+            .map(|issue| {
+                [
+                    issue.app.clone(),
+                    issue.service.to_string(),
+                    issue.name.to_string(),
+                    issue.problem.as_str().to_string(),
+                ]
+            })
+            .collect();
+        print_table(&["APP", "SERVICE", "VARIABLE", "PROBLEM"], &rows);
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::EnvCheckFailed(issues.len()).into())
+    }
+}
+
+/// Parses `KEY=value` lines of a dotenv file, ignoring comments and blanks.
+pub fn parse_env(content: &str) -> BTreeMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn block_start(service: &Service) -> String {
+    format!("# >>> {}", service)
+}
+
+fn block_end(service: &Service) -> String {
+    format!("# <<< {}", service)
+}
+
+fn merge_env_block(content: &str, service: &Service, vars: &[&EnvVar]) -> String {
+    let existing = parse_env(content);
+    let missing: Vec<&&EnvVar> = vars
+        .iter()
+        .filter(|var| !existing.contains_key(var.name))
+        .collect();
+
+    if missing.is_empty() {
+        return content.to_string();
+    }
+
+    let mut result = content.to_string();
+    if !result.is_empty() {
+        if !result.ends_with('\n') {
+            result.push('\n');
+        }
+        result.push('\n');
+    }
+
+    result.push_str(&block_start(service));
+    result.push('\n');
+    for var in missing {
+        let requirement = if var.required { "required" } else { "optional" };
+        result.push_str(&format!(
+            "# {} ({})\n{}=\n",
+            var.description, requirement, var.name
+        ));
+    }
+    result.push_str(&block_end(service));
+    result.push('\n');
+    result
+}
+
+fn strip_env_block(content: &str, service: &Service, names: &[&str]) -> String {
+    let start = block_start(service);
+    let end = block_end(service);
+
+    let mut lines: Vec<&str> = Vec::new();
+    let mut in_block = false;
+    for line in content.lines() {
+        if line.trim() == start {
+            in_block = true;
+            continue;
+        }
+        if in_block {
+            if line.trim() == end {
+                in_block = false;
+            }
+            continue;
+        }
+
+        let key = line
+            .trim()
+            .strip_prefix("export ")
+            .unwrap_or(line.trim())
+            .split('=')
+            .next()
+            .unwrap_or("")
+            .trim();
+        if line.contains('=') && names.contains(&key) {
+            continue;
+        }
+        lines.push(line);
+    }
+
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    if lines.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_and_strip_env_block() {
+        let original = "DATABASE_URL=\n";
+        let service = Service::Trigger;
+        let vars = service.env_vars_for_app("app");
+
+        let merged = merge_env_block(original, &service, &vars);
+        assert_eq!(
+            merged,
+            "DATABASE_URL=\n\n# >>> trigger\n# Trigger.dev project id (required)\nTRIGGER_PROJECT_ID=\n# Trigger.dev secret key (required)\nTRIGGER_SECRET_KEY=\n# <<< trigger\n"
+        );
+        // merging twice doesn't duplicate
+        assert_eq!(merge_env_block(&merged, &service, &vars), merged);
+
+        let names: Vec<&str> = vars.iter().map(|var| var.name).collect();
+        assert_eq!(strip_env_block(&merged, &service, &names), original);
+    }
+
+    #[test]
+    fn test_merge_skips_declared_vars() {
+        let original = "TRIGGER_PROJECT_ID=abc";
+        let service = Service::Trigger;
+        let merged = merge_env_block(original, &service, &service.env_vars_for_app("app"));
+        assert!(merged.starts_with("TRIGGER_PROJECT_ID=abc\n\n# >>> trigger\n"));
+        assert_eq!(merged.matches("TRIGGER_PROJECT_ID=").count(), 1);
+    }
+
+    #[test]
+    fn test_strip_env_values() {
+        let content = "export RESEND_API_KEY=\"re_123\"\nOTHER=1\n";
+        let stripped = strip_env_block(content, &Service::Resend, &["RESEND_API_KEY"]);
+        assert_eq!(stripped, "OTHER=1\n");
+    }
+
+    #[test]
+    fn test_check_app_env() {
+        let mut env = BTreeMap::new();
+        env.insert("TRIGGER_PROJECT_ID".to_string(), "".to_string());
+        env.insert("TRIGGER_SECRET_KEY".to_string(), "sk_123".to_string());
+
+        let issues = check_app_env("app", &Service::Trigger, &env).unwrap();
+        let problems: Vec<(&str, EnvProblem)> =
+            issues.iter().map(|i| (i.name, i.problem)).collect();
+        assert_eq!(
+            problems,
+            vec![
+                ("TRIGGER_PROJECT_ID", EnvProblem::Empty),
+                ("TRIGGER_SECRET_KEY", EnvProblem::Malformed),
+            ]
+        );
+
+        env.insert("TRIGGER_PROJECT_ID".to_string(), "proj_123".to_string());
+        env.insert("TRIGGER_SECRET_KEY".to_string(), "tr_dev_123".to_string());
+        assert!(check_app_env("app", &Service::Trigger, &env)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_optional_vars_may_be_missing() {
+        let mut env = BTreeMap::new();
+        env.insert(
+            "NEXT_PUBLIC_SENTRY_DSN".to_string(),
+            "https://abc@o1.ingest.sentry.io/123".to_string(),
+        );
+        assert!(check_app_env("web", &Service::Sentry, &env)
+            .unwrap()
+            .is_empty());
+
+        env.remove("NEXT_PUBLIC_SENTRY_DSN");
+        let issues = check_app_env("web", &Service::Sentry, &env).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].problem, EnvProblem::Missing);
+    }
+
+    #[test]
+    fn test_parse_env() {
+        let env = parse_env("# comment\nA=1\nexport B=\"two\"\nC='3'\n\nD=\n");
+        assert_eq!(env.get("A").map(String::as_str), Some("1"));
+        assert_eq!(env.get("B").map(String::as_str), Some("two"));
+        assert_eq!(env.get("C").map(String::as_str), Some("3"));
+        assert_eq!(env.get("D").map(String::as_str), Some(""));
+    }
+
+    #[test]
+    fn test_fill_service_env_asks_only_for_missing_variables() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path();
+        for app in ["web", "app"] {
+            fs::create_dir_all(app_dir(project, app)).unwrap();
+        }
+        let sentry = "SENTRY_AUTH_TOKEN=token\nSENTRY_ORG=acme\nSENTRY_PROJECT=web\n";
+        fs::write(
+            app_dir(project, "app").join(".env.local"),
+            format!(
+                "TRIGGER_PROJECT_ID=abc\n{}NEXT_PUBLIC_SENTRY_DSN=dsn",
+                sentry
+            ),
+        )
+        .unwrap();
+        fs::write(app_dir(project, "web").join(".env.local"), sentry).unwrap();
+
+        let mut asked = Vec::new();
+        for service in [Service::Trigger, Service::Sentry] {
+            fill_service_env(project, &service, |var| {
+                asked.push(var.name);
+                Ok(format!("{}-value", var.name.to_lowercase()))
+            })
+            .unwrap();
+        }
+        let read = |app| fs::read_to_string(app_dir(project, app).join(".env.local")).unwrap();
+
+        assert_eq!(asked, ["TRIGGER_SECRET_KEY", "NEXT_PUBLIC_SENTRY_DSN"]);
+        assert_eq!(
+            read("app"),
+            format!(
+                "TRIGGER_PROJECT_ID=abc\n{}NEXT_PUBLIC_SENTRY_DSN=dsn\nTRIGGER_SECRET_KEY=trigger_secret_key-value\n",
+                sentry
+            )
+        );
+        assert_eq!(
+            read("web"),
+            format!(
+                "{}NEXT_PUBLIC_SENTRY_DSN=next_public_sentry_dsn-value\n",
+                sentry
+            )
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::{fmt, path::PathBuf};
+
+/// The failures callers may want to tell apart, each with a stable code.
+/// They travel inside `anyhow::Error`, use `error_code` to get the code of
+/// any error.
+#[derive(Debug)]
+pub enum Error {
+    /// An argument, answer or config value is invalid.
+    InvalidInput(String),
+    /// A prompt needs an answer but prompts are disabled.
+    MissingAnswer(String),
+    /// The project directory already exists and isn't empty.
+    ProjectExists(PathBuf),
+    /// No V1 project was found from the given directory.
+    NotAProject(PathBuf),
+    TemplateNotFound(String),
+    TemplateRender {
+        template: String,
+        message: String,
+    },
+    InvalidPackageJson {
+        path: PathBuf,
+        message: String,
+    },
+    InstallFailed {
+        path: PathBuf,
+        message: String,
+    },
+    /// Environment variables are missing or malformed.
+    EnvCheckFailed(usize),
+    /// `doctor` found problems it couldn't fix.
+    DoctorFailed(usize),
+    /// The command was stopped, e.g. by ctrl+c, and rolled back.
+    Cancelled,
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::InvalidInput(_) => "E_INVALID_INPUT",
+            Error::MissingAnswer(_) => "E_MISSING_ANSWER",
+            Error::ProjectExists(_) => "E_PROJECT_EXISTS",
+            Error::NotAProject(_) => "E_NOT_A_PROJECT",
+            Error::TemplateNotFound(_) => "E_TEMPLATE_NOT_FOUND",
+            Error::TemplateRender { .. } => "E_TEMPLATE_RENDER",
+            Error::InvalidPackageJson { .. } => "E_INVALID_PACKAGE_JSON",
+            Error::InstallFailed { .. } => "E_INSTALL_FAILED",
+            Error::EnvCheckFailed(_) => "E_ENV_CHECK_FAILED",
+            Error::DoctorFailed(_) => "E_DOCTOR_FAILED",
+            Error::Cancelled => "E_CANCELLED",
+        }
+    }
+
+    /// The process exit code, 1 is left for errors without a code.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidInput(_) => 2,
+            Error::MissingAnswer(_) => 3,
+            Error::ProjectExists(_) => 4,
+            Error::NotAProject(_) => 5,
+            Error::TemplateNotFound(_) => 6,
+            Error::TemplateRender { .. } => 7,
+            Error::InvalidPackageJson { .. } => 8,
+            Error::InstallFailed { .. } => 9,
+            Error::EnvCheckFailed(_) => 10,
+            Error::DoctorFailed(_) => 11,
+            // as shells report a process stopped by SIGINT
+            Error::Cancelled => 130,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidInput(message) | Error::MissingAnswer(message) => {
+                write!(f, "{}", message)
+            }
+            Error::ProjectExists(path) => write!(
+                f,
+                "Directory {} already exists and is not empty",
+                path.display()
+            ),
+            Error::NotAProject(path) => write!(
+                f,
+                "No V1 project found in {} or any of its parents, run the command inside a project or pass --cwd",
+                path.display()
+            ),
+            Error::TemplateNotFound(template) => write!(f, "Template not found: {}", template),
+            Error::TemplateRender { template, message } => {
+                write!(f, "Failed to render template {}: {}", template, message)
+            }
+            Error::InvalidPackageJson { path, message } => {
+                write!(f, "Invalid {}: {}", path.display(), message)
+            }
+            Error::InstallFailed { path, message } => {
+                write!(f, "Installation failed in {}: {}", path.display(), message)
+            }
+            Error::EnvCheckFailed(count) => {
+                write!(f, "{} environment variable problem(s) found", count)
+            }
+            Error::DoctorFailed(count) => write!(f, "{} problem(s) found in the project", count),
+            Error::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn invalid_input(message: impl Into<String>) -> anyhow::Error {
+    Error::InvalidInput(message.into()).into()
+}
+
+pub const UNKNOWN_ERROR_CODE: &str = "E_UNKNOWN";
+
+/// The typed error anywhere in the chain of `error`, if any.
+pub fn find_error(error: &anyhow::Error) -> Option<&Error> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<Error>())
+}
+
+pub fn error_code(error: &anyhow::Error) -> &'static str {
+    find_error(error).map_or(UNKNOWN_ERROR_CODE, Error::code)
+}
+
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    find_error(error).map_or(1, Error::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_code_survives_context() {
+        let error = Err::<(), _>(Error::TemplateNotFound("services/cal".to_string()))
+            .context("Failed to add cal")
+            .unwrap_err();
+        assert_eq!(error_code(&error), "E_TEMPLATE_NOT_FOUND");
+        assert_eq!(exit_code(&error), 6);
+
+        let error = anyhow::anyhow!("something else");
+        assert_eq!(error_code(&error), UNKNOWN_ERROR_CODE);
+        assert_eq!(exit_code(&error), 1);
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{cell::RefCell, io::Write, path::Path, rc::Rc, time::Instant};
+
+use crate::{error::error_code, resolver::ServicePlan};
+
+#[derive(ValueEnum, Clone, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    #[clap(help = "Human readable output")]
+    Text,
+    #[clap(help = "Newline delimited JSON events on stdout")]
+    Json,
+}
+
+type Listener = Box<dyn FnMut(&serde_json::Value)>;
+
+thread_local! {
+    // per thread, so concurrent generations each get their own events
+    static LISTENER: RefCell<Option<Listener>> = RefCell::new(None);
+}
+
+/// Something that happened while generating files, emitted as one JSON line
+/// with `--output json`.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    ServicesResolved {
+        #[serde(flatten)]
+        plan: &'a ServicePlan,
+    },
+    WorkspaceStarted {
+        name: &'a str,
+        path: &'a Path,
+    },
+    WorkspaceFinished {
+        name: &'a str,
+        path: &'a Path,
+        files_written: usize,
+    },
+    FileWritten {
+        path: &'a Path,
+        source: &'a Path,
+    },
+    FileSkipped {
+        source: &'a Path,
+        reason: &'a str,
+    },
+    ServiceAdded {
+        name: &'a str,
+        path: &'a Path,
+    },
+    InstallStarted {
+        path: &'a Path,
+        package_manager: &'a str,
+    },
+    InstallOutput {
+        path: &'a Path,
+        stream: &'a str,
+        line: &'a str,
+    },
+    InstallFinished {
+        path: &'a Path,
+        success: bool,
+        duration_ms: u128,
+    },
+}
+
+/// Calls `on_event` with every event emitted on this thread while `f`
+/// runs, shaped as with `--output json` but without `elapsed_ms`.
+pub fn with_progress<T>(
+    on_event: impl FnMut(&serde_json::Value) + 'static,
+    f: impl FnOnce() -> T,
+) -> T {
+    let previous = LISTENER.with(|listener| listener.borrow_mut().replace(Box::new(on_event)));
+    let result = f();
+    LISTENER.with(|listener| *listener.borrow_mut() = previous);
+    result
+}
+
+fn has_listener() -> bool {
+    LISTENER.with(|listener| listener.borrow().is_some())
+}
+
+pub fn emit(event: Event) {
+    if !has_listener() {
+        return;
+    }
+    let Ok(value) = serde_json::to_value(&event) else {
+        return;
+    };
+
+    LISTENER.with(|listener| {
+        if let Some(on_event) = listener.borrow_mut().as_mut() {
+            on_event(&value);
+        }
+    });
+}
+
+/// The files touched by the command, sent with the `finished` event.
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    files_written: Vec<serde_json::Value>,
+    files_skipped: Vec<serde_json::Value>,
+}
+
+impl Summary {
+    fn record(&mut self, event: &serde_json::Value) {
+        match event["event"].as_str() {
+            Some("file_written") => self.files_written.push(event["path"].clone()),
+            Some("file_skipped") => self.files_skipped.push(event["source"].clone()),
+            _ => {}
+        }
+    }
+}
+
+/// Where a run prints its events with `--output json`, one JSON line each.
+struct JsonOutput {
+    out: Box<dyn Write>,
+    start: Instant,
+    summary: Summary,
+}
+
+impl JsonOutput {
+    fn print_line(&mut self, mut value: serde_json::Value) {
+        value["elapsed_ms"] = serde_json::json!(self.start.elapsed().as_millis());
+        let _ = writeln!(self.out, "{}", value);
+    }
+}
+
+/// Writes every event emitted on this thread while `f` runs to `out` as a
+/// JSON line. With `summarize` the stream ends with a `finished` event
+/// summing up the command, the files it wrote and skipped.
+pub fn with_json_output(
+    out: Box<dyn Write>,
+    summarize: bool,
+    f: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let output = Rc::new(RefCell::new(JsonOutput {
+        out,
+        start: Instant::now(),
+        summary: Summary::default(),
+    }));
+
+    let printer = output.clone();
+    let result = with_progress(
+        move |event| {
+            let mut printer = printer.borrow_mut();
+            printer.summary.record(event);
+            printer.print_line(event.clone());
+        },
+        f,
+    );
+
+    if summarize {
+        let mut output = output.borrow_mut();
+        let summary = std::mem::take(&mut output.summary);
+        output.print_line(serde_json::json!({
+            "event": "finished",
+            "success": result.is_ok(),
+            "error": result.as_ref().err().map(|e| e.to_string()),
+            "code": result.as_ref().err().map(error_code),
+            "files_written": summary.files_written,
+            "files_skipped": summary.files_skipped,
+        }));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_listener_gets_events_of_its_call() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let received = events.clone();
+        let path = Path::new("acme/package.json");
+
+        with_progress(
+            move |event| received.borrow_mut().push(event["event"].clone()),
+            || {
+                emit(Event::FileWritten { path, source: path });
+                emit(Event::WorkspaceFinished {
+                    name: "root",
+                    path,
+                    files_written: 1,
+                });
+            },
+        );
+        emit(Event::FileWritten { path, source: path });
+
+        assert_eq!(*events.borrow(), ["file_written", "workspace_finished"]);
+        assert!(!has_listener());
+    }
+}
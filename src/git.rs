@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::logger::{log_debug, log_trace};
+
+pub const DEFAULT_COMMIT_MESSAGE: &str = "Initial commit from create-v1-app";
+
+/// Directories the apps' tools build into, ignored by git and left out
+/// when a command walks the project.
+pub const BUILD_DIRS: &[&str] = &[
+    ".next", ".turbo", ".vercel", "dist", "build", "out", "coverage",
+];
+
+/// The `.gitignore` of a new project, with what the package manager adds.
+pub fn gitignore(package_manager: &str) -> String {
+    let mut content = format!(
+        "# dependencies\n\
+         node_modules\n\
+         \n\
+         # builds\n\
+         {}\n\
+         *.tsbuildinfo\n\
+         \n\
+         # environment\n\
+         .env\n\
+         .env*.local\n\
+         \n\
+         # logs\n\
+         *.log\n\
+         \n\
+         # misc\n\
+         .DS_Store\n",
+        BUILD_DIRS.join("\n")
+    );
+
+    let extra = match package_manager {
+        "yarn" => "\n# yarn\n.yarn/*\n!.yarn/patches\n!.yarn/plugins\n!.yarn/releases\n.pnp.*\n",
+        "pnpm" => "\n# pnpm\n.pnpm-store\n",
+        _ => "",
+    };
+    content.push_str(extra);
+    content
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    git_with_env(dir, args, &[])
+}
+
+fn git_with_env(dir: &Path, args: &[&str], envs: &[(&str, &str)]) -> Result<String> {
+    log_debug(&format!("Running: git {}", args.join(" ")));
+    let output = Command::new("git")
+        .args(args)
+        .envs(envs.iter().copied())
+        .current_dir(dir)
+        .output()
+        .map_err(|e| anyhow!("Failed to run git: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    log_trace(&format!(
+        "git {} output:\nStdout: {}\nStderr: {}",
+        args.join(" "),
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    ));
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(stdout)
+}
+
+/// Whether `dir` is inside a git work tree. False as well when git isn't
+/// installed.
+pub fn is_inside_work_tree(dir: &Path) -> bool {
+    git(dir, &["rev-parse", "--is-inside-work-tree"]).is_ok_and(|out| out.trim() == "true")
+}
+
+/// Initializes a repository in `project_dir` and commits every file in it.
+/// Writes a `.gitignore` first unless the templates brought one, returning
+/// its path when it did.
+pub fn init_repository(
+    project_dir: &Path,
+    package_manager: &str,
+    message: &str,
+) -> Result<Option<PathBuf>> {
+    init_repository_with_env(project_dir, package_manager, message, &[])
+}
+
+/// `init_repository` with extra environment variables for the commit, e.g.
+/// the identity to commit as.
+fn init_repository_with_env(
+    project_dir: &Path,
+    package_manager: &str,
+    message: &str,
+    envs: &[(&str, &str)],
+) -> Result<Option<PathBuf>> {
+    let gitignore_path = project_dir.join(".gitignore");
+    let written = if gitignore_path.exists() {
+        None
+    } else {
+        fs::write(&gitignore_path, gitignore(package_manager))?;
+        Some(gitignore_path)
+    };
+
+    git(project_dir, &["init", "--quiet"])?;
+    git(project_dir, &["add", "--all"])?;
+    git_with_env(
+        project_dir,
+        &["commit", "--quiet", "--message", message],
+        envs,
+    )?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // commits need an identity, which CI machines may not have
+    const IDENTITY: &[(&str, &str)] = &[
+        ("GIT_AUTHOR_NAME", "V1"),
+        ("GIT_AUTHOR_EMAIL", "v1@example.com"),
+        ("GIT_COMMITTER_NAME", "V1"),
+        ("GIT_COMMITTER_EMAIL", "v1@example.com"),
+    ];
+
+    #[test]
+    fn test_init_repository_commits_everything() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("node_modules/react")).unwrap();
+        fs::write(dir.join("package.json"), "{}\n").unwrap();
+        fs::write(dir.join("node_modules/react/index.js"), "\n").unwrap();
+
+        let gitignore = init_repository_with_env(dir, "pnpm", "Initial commit", IDENTITY).unwrap();
+        let files = git(dir, &["ls-files"]).unwrap();
+        let log = git(dir, &["log", "--format=%s"]).unwrap();
+        let nested = dir.join("apps");
+        fs::create_dir_all(&nested).unwrap();
+        let inside = is_inside_work_tree(&nested);
+
+        assert_eq!(gitignore, Some(dir.join(".gitignore")));
+        assert_eq!(files, ".gitignore\npackage.json\n");
+        assert_eq!(log, "Initial commit\n");
+        assert!(inside);
+    }
+}
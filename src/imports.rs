@@ -0,0 +1,473 @@
+use anyhow::{anyhow, Result};
+use oxc_allocator::Allocator;
+use oxc_ast::ast::{
+    CallExpression, ExportAllDeclaration, ExportNamedDeclaration, Expression, ImportDeclaration,
+    ImportExpression, Program, Statement, StringLiteral, TSExternalModuleReference,
+    TSModuleReference,
+};
+use oxc_ast_visit::{walk, Visit};
+use oxc_parser::Parser;
+use oxc_semantic::SemanticBuilder;
+use oxc_span::{GetSpan, SourceType, Span};
+use std::path::Path;
+
+/// A use of a package that could not be removed automatically and has to be
+/// fixed by hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingReference {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The string literal naming a module in an import, export, `import()` or
+/// `require()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleSpecifier {
+    pub value: String,
+    /// Byte range of the literal, quotes included.
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+pub struct ImportRemoval {
+    pub source: String,
+    pub removed: usize,
+    pub dangling: Vec<DanglingReference>,
+}
+
+/// Removes every import, re-export and top level `require()` of `package` (or
+/// one of its subpaths) from a TS/JS source file.
+///
+/// Uses of the removed bindings and any dynamic `import()`/nested `require()`
+/// of the package are reported as dangling references against the new source.
+pub fn remove_package_imports(path: &Path, source: &str, package: &str) -> Result<ImportRemoval> {
+    let source_type = SourceType::from_path(path)
+        .map_err(|e| anyhow!("Unsupported file {}: {}", path.display(), e))?;
+
+    let allocator = Allocator::default();
+    let parsed = Parser::new(&allocator, source, source_type).parse();
+    if parsed.panicked || !parsed.errors.is_empty() {
+        return Err(parse_error(path, &parsed.errors));
+    }
+
+    let (spans, bindings) = collect_package_statements(&parsed.program, package);
+    if spans.is_empty() && !source.contains(package) {
+        return Ok(ImportRemoval {
+            source: source.to_string(),
+            removed: 0,
+            dangling: Vec::new(),
+        });
+    }
+
+    let new_source = remove_spans(source, &spans);
+    let dangling = find_dangling_references(&new_source, source_type, package, &bindings);
+
+    Ok(ImportRemoval {
+        source: new_source,
+        removed: spans.len(),
+        dangling,
+    })
+}
+
+fn parse_error<E: ToString>(path: &Path, errors: &[E]) -> anyhow::Error {
+    anyhow!(
+        "Failed to parse {}: {}",
+        path.display(),
+        errors
+            .first()
+            .map(|e| e.to_string())
+            .unwrap_or_else(|| "unknown syntax error".to_string())
+    )
+}
+
+/// Every module specifier in a TS/JS source file, in source order.
+pub fn module_specifiers(path: &Path, source: &str) -> Result<Vec<ModuleSpecifier>> {
+    let source_type = SourceType::from_path(path)
+        .map_err(|e| anyhow!("Unsupported file {}: {}", path.display(), e))?;
+
+    let allocator = Allocator::default();
+    let parsed = Parser::new(&allocator, source, source_type).parse();
+    if parsed.panicked || !parsed.errors.is_empty() {
+        return Err(parse_error(path, &parsed.errors));
+    }
+
+    let mut collector = SpecifierCollector {
+        source,
+        specifiers: Vec::new(),
+    };
+    collector.visit_program(&parsed.program);
+    collector
+        .specifiers
+        .sort_by_key(|specifier| specifier.start);
+    Ok(collector.specifiers)
+}
+
+/// Replaces the specifiers `rename` returns a new name for, keeping their
+/// quotes. Returns the new source and how many were renamed.
+pub fn rename_module_specifiers(
+    path: &Path,
+    source: &str,
+    rename: impl Fn(&str) -> Option<String>,
+) -> Result<(String, usize)> {
+    let mut result = source.to_string();
+    let mut renamed = 0;
+    for specifier in module_specifiers(path, source)?.iter().rev() {
+        if let Some(value) = rename(&specifier.value) {
+            let quote = &source[specifier.start..specifier.start + 1];
+            result.replace_range(
+                specifier.start..specifier.end,
+                &format!("{}{}{}", quote, value, quote),
+            );
+            renamed += 1;
+        }
+    }
+    Ok((result, renamed))
+}
+
+fn matches_package(specifier: &str, package: &str) -> bool {
+    specifier == package
+        || specifier
+            .strip_prefix(package)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn collect_package_statements(program: &Program, package: &str) -> (Vec<Span>, Vec<String>) {
+    let mut spans = Vec::new();
+    let mut bindings = Vec::new();
+
+    for statement in &program.body {
+        match statement {
+            Statement::ImportDeclaration(decl) if matches_package(&decl.source.value, package) => {
+                spans.push(decl.span);
+                if let Some(specifiers) = &decl.specifiers {
+                    bindings.extend(specifiers.iter().map(|s| s.local().name.to_string()));
+                }
+            }
+            Statement::ExportNamedDeclaration(decl)
+                if decl
+                    .source
+                    .as_ref()
+                    .is_some_and(|s| matches_package(&s.value, package)) =>
+            {
+                spans.push(decl.span);
+            }
+            Statement::ExportAllDeclaration(decl)
+                if matches_package(&decl.source.value, package) =>
+            {
+                spans.push(decl.span);
+            }
+            Statement::TSImportEqualsDeclaration(decl) => {
+                if let TSModuleReference::ExternalModuleReference(reference) =
+                    &decl.module_reference
+                {
+                    if matches_package(&reference.expression.value, package) {
+                        spans.push(decl.span);
+                        bindings.push(decl.id.name.to_string());
+                    }
+                }
+            }
+            Statement::VariableDeclaration(decl) => {
+                // only drop the whole statement when every declarator is a require
+                // of the package, otherwise it is reported as a dangling use
+                let all_required = !decl.declarations.is_empty()
+                    && decl.declarations.iter().all(|declarator| {
+                        matches!(
+                            &declarator.init,
+                            Some(Expression::CallExpression(call))
+                                if call
+                                    .common_js_require()
+                                    .is_some_and(|s| matches_package(&s.value, package))
+                        )
+                    });
+                if all_required {
+                    spans.push(decl.span);
+                    for declarator in &decl.declarations {
+                        bindings.extend(
+                            declarator
+                                .id
+                                .get_binding_identifiers()
+                                .iter()
+                                .map(|id| id.name.to_string()),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (spans, bindings)
+}
+
+/// Removes the given spans, also dropping the rest of the line when a span
+/// occupied it on its own.
+fn remove_spans(source: &str, spans: &[Span]) -> String {
+    let mut ranges: Vec<(usize, usize)> = spans
+        .iter()
+        .map(|span| expand_to_line(source, span.start as usize, span.end as usize))
+        .collect();
+    ranges.sort();
+
+    let mut result = String::with_capacity(source.len());
+    let mut cursor = 0;
+    for (start, end) in ranges {
+        if start < cursor {
+            continue;
+        }
+        result.push_str(&source[cursor..start]);
+        cursor = end;
+    }
+    result.push_str(&source[cursor..]);
+    result
+}
+
+fn expand_to_line(source: &str, start: usize, end: usize) -> (usize, usize) {
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[end..]
+        .find('\n')
+        .map_or(source.len(), |i| end + i + 1);
+
+    let before_is_blank = source[line_start..start].trim().is_empty();
+    let after_is_blank = source[end..line_end].trim().is_empty();
+
+    if before_is_blank && after_is_blank {
+        (line_start, line_end)
+    } else {
+        (start, end)
+    }
+}
+
+fn find_dangling_references(
+    source: &str,
+    source_type: SourceType,
+    package: &str,
+    bindings: &[String],
+) -> Vec<DanglingReference> {
+    let allocator = Allocator::default();
+    let parsed = Parser::new(&allocator, source, source_type).parse();
+    if parsed.panicked {
+        return Vec::new();
+    }
+
+    let semantic = SemanticBuilder::new().build(&parsed.program).semantic;
+    let scoping = semantic.scoping();
+
+    let mut spans: Vec<(String, Span)> = Vec::new();
+    for name in bindings {
+        if let Some(reference_ids) = scoping.root_unresolved_references().get(name.as_str()) {
+            for reference_id in reference_ids {
+                let reference = scoping.get_reference(*reference_id);
+                spans.push((name.clone(), semantic.reference_span(reference)));
+            }
+        }
+    }
+
+    let mut finder = PackageUseFinder {
+        package,
+        uses: Vec::new(),
+    };
+    finder.visit_program(&parsed.program);
+    spans.extend(finder.uses);
+
+    spans.sort_by_key(|(_, span)| span.start);
+    spans
+        .into_iter()
+        .map(|(name, span)| {
+            let (line, column) = line_column(source, span.start as usize);
+            DanglingReference { name, line, column }
+        })
+        .collect()
+}
+
+/// Finds dynamic `import()` and `require()` calls of the package that are not
+/// top level declarations and therefore can't be removed safely.
+struct PackageUseFinder<'p> {
+    package: &'p str,
+    uses: Vec<(String, Span)>,
+}
+
+impl<'a> Visit<'a> for PackageUseFinder<'_> {
+    fn visit_import_expression(&mut self, it: &ImportExpression<'a>) {
+        if let Expression::StringLiteral(source) = &it.source {
+            if matches_package(&source.value, self.package) {
+                self.uses
+                    .push((format!("import(\"{}\")", source.value), it.span));
+            }
+        }
+        walk::walk_import_expression(self, it);
+    }
+
+    fn visit_call_expression(&mut self, it: &CallExpression<'a>) {
+        if let Some(source) = it.common_js_require() {
+            if matches_package(&source.value, self.package) {
+                self.uses
+                    .push((format!("require(\"{}\")", source.value), it.span()));
+            }
+        }
+        walk::walk_call_expression(self, it);
+    }
+}
+
+struct SpecifierCollector<'s> {
+    source: &'s str,
+    specifiers: Vec<ModuleSpecifier>,
+}
+
+impl SpecifierCollector<'_> {
+    fn push(&mut self, literal: &StringLiteral) {
+        let start = literal.span.start as usize;
+        self.specifiers.push(ModuleSpecifier {
+            value: literal.value.to_string(),
+            start,
+            end: literal.span.end as usize,
+            line: line_column(self.source, start).0,
+        });
+    }
+}
+
+impl<'a> Visit<'a> for SpecifierCollector<'_> {
+    fn visit_import_declaration(&mut self, it: &ImportDeclaration<'a>) {
+        self.push(&it.source);
+    }
+
+    fn visit_export_named_declaration(&mut self, it: &ExportNamedDeclaration<'a>) {
+        if let Some(source) = &it.source {
+            self.push(source);
+        }
+        walk::walk_export_named_declaration(self, it);
+    }
+
+    fn visit_export_all_declaration(&mut self, it: &ExportAllDeclaration<'a>) {
+        self.push(&it.source);
+    }
+
+    fn visit_ts_external_module_reference(&mut self, it: &TSExternalModuleReference<'a>) {
+        self.push(&it.expression);
+    }
+
+    fn visit_import_expression(&mut self, it: &ImportExpression<'a>) {
+        if let Expression::StringLiteral(source) = &it.source {
+            self.push(source);
+        }
+        walk::walk_import_expression(self, it);
+    }
+
+    fn visit_call_expression(&mut self, it: &CallExpression<'a>) {
+        if let Some(source) = it.common_js_require() {
+            self.push(source);
+        }
+        walk::walk_call_expression(self, it);
+    }
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rfind('\n')
+        .map_or(before.chars().count(), |i| before[i + 1..].chars().count())
+        + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remove(source: &str) -> ImportRemoval {
+        remove_package_imports(Path::new("index.tsx"), source, "@v1/resend").unwrap()
+    }
+
+    #[test]
+    fn test_removes_multi_line_imports_and_reexports() {
+        let source = r#"import { a } from "@v1/ui";
+import {
+  sendEmail,
+  type Email,
+} from "@v1/resend";
+import * as templates from "@v1/resend/templates";
+export { render } from "@v1/resend";
+export * from "@v1/resend/client";
+
+export const x = a;
+"#;
+        let result = remove(source);
+        assert_eq!(result.removed, 4);
+        assert_eq!(
+            result.source,
+            "import { a } from \"@v1/ui\";\n\nexport const x = a;\n"
+        );
+        assert!(result.dangling.is_empty());
+    }
+
+    #[test]
+    fn test_removes_require_and_reports_uses() {
+        let source = r#"const { sendEmail } = require("@v1/resend");
+const resendLike = require("@v1/resend-like");
+
+export async function handler() {
+  await sendEmail();
+  const mod = await import("@v1/resend");
+  return resendLike;
+}
+"#;
+        let result = remove(source);
+        assert_eq!(result.removed, 1);
+        assert!(result.source.starts_with("const resendLike"));
+        assert_eq!(
+            result.dangling,
+            vec![
+                DanglingReference {
+                    name: "sendEmail".to_string(),
+                    line: 4,
+                    column: 9,
+                },
+                DanglingReference {
+                    name: "import(\"@v1/resend\")".to_string(),
+                    line: 5,
+                    column: 21,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shadowed_bindings_are_not_reported() {
+        let source = r#"import { client } from "@v1/resend";
+
+export function run(client: string) {
+  return client;
+}
+"#;
+        let result = remove(source);
+        assert!(result.dangling.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_source_is_an_error() {
+        assert!(remove_package_imports(Path::new("a.ts"), "import {", "@v1/resend").is_err());
+    }
+
+    #[test]
+    fn test_module_specifiers_of_every_kind() {
+        let source = r#"import { a } from "@v1/a";
+export * from '@v1/b';
+export { c } from "@v1/c/client";
+import d = require("@v1/d");
+const e = require("@v1/e");
+async function load() {
+  return import("@v1/f");
+}
+"#;
+        let specifiers = module_specifiers(Path::new("index.ts"), source).unwrap();
+        let values: Vec<&str> = specifiers.iter().map(|s| s.value.as_str()).collect();
+        assert_eq!(
+            values,
+            ["@v1/a", "@v1/b", "@v1/c/client", "@v1/d", "@v1/e", "@v1/f"]
+        );
+        assert_eq!(&source[specifiers[1].start..specifiers[1].end], "'@v1/b'");
+        assert_eq!(specifiers[5].line, 7);
+    }
+}
@@ -1,49 +1,95 @@
 use anyhow::Result;
-use once_cell::sync::Lazy;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
+use std::io::Write;
+use std::path::PathBuf;
 
+mod answers;
 mod app;
+mod blueprint;
+mod cancel;
 mod cleanup;
 mod cli;
+mod config;
+mod context;
+mod diff;
+mod doctor;
+mod env;
+mod error;
+mod events;
+mod git;
+mod imports;
+mod list;
+mod lockfile;
 mod logger;
+mod package_json;
+mod project;
+mod prompt;
+mod rename;
+mod resolver;
+mod scaffold;
 mod service;
 mod tera;
 mod utils;
 mod workspace;
 
-static CLEANUP_NEEDED: AtomicBool = AtomicBool::new(false);
-static CLEANUP_MANAGER: Lazy<Mutex<cleanup::CleanupManager>> =
-    Lazy::new(|| Mutex::new(cleanup::CleanupManager::new()));
+pub use blueprint::{Blueprint, WorkspaceKind};
+pub use cancel::{with_cancellation, CancellationToken};
+pub use cli::Service;
+pub use error::{error_code, exit_code, invalid_input, Error};
+pub use events::with_progress;
+pub use list::{list_services, ServiceInfo};
+pub use project::{add_services, add_workspace, remove_services};
+pub use scaffold::{Scaffold, ScaffoldReport};
+pub use utils::bundled_templates_path;
 
-fn perform_cleanup() {
-    let manager = CLEANUP_MANAGER.lock().unwrap();
-    manager.cleanup();
+/// The full log of the last run on this thread, to point users at when it
+/// fails.
+pub fn log_file_path() -> Option<PathBuf> {
+    logger::log_file_path()
 }
 
-pub fn set_cleanup_needed() {
-    CLEANUP_NEEDED.store(true, Ordering::SeqCst);
-    perform_cleanup();
+/// Cancels every command running in the process. They stop at their next
+/// check, roll back what they wrote and fail with `Error::Cancelled`.
+/// Commands started afterwards aren't affected.
+pub fn cancel() {
+    cancel::cancel_all();
 }
 
+/// Runs the CLI with the arguments of the process.
 pub fn run() -> Result<()> {
-    logger::initialize_logger()?;
-
-    ctrlc::set_handler(|| {
-        CLEANUP_NEEDED.store(true, Ordering::SeqCst);
-        perform_cleanup();
-        std::process::exit(1);
-    })?;
-
-    let args: Vec<String> = std::env::args().collect();
+    run_with_args(std::env::args().skip(1))
+}
 
-    cli::parse_cli(args)?;
+/// Runs the CLI with `args`, which don't include the program name. Can be
+/// called repeatedly and concurrently in one process: each run has its own
+/// flags and settings, and a failed one only rolls back what it wrote.
+pub fn run_with_args<I, T>(args: I) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<String>,
+{
+    run_with_output(args, Box::new(std::io::stdout()))
+}
 
-    if CLEANUP_NEEDED.load(Ordering::SeqCst) {
-        perform_cleanup();
-    }
+/// Runs the CLI with `args`, printing the events of `--output json` to
+/// `events`.
+fn run_with_output<I, T>(args: I, events: Box<dyn Write>) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<String>,
+{
+    let args: Vec<String> = std::iter::once(env!("CARGO_PKG_NAME").to_string())
+        .chain(args.into_iter().map(Into::into))
+        .collect();
 
-    Ok(())
+    logger::with_run_log(&args, || {
+        let result = with_cancellation(&CancellationToken::new(), || {
+            cli::parse_cli(args.clone(), events)
+        });
+        if let Err(e) = &result {
+            logger::log_trace(&format!("Run failed: {:?}", e));
+        }
+        result
+    })
 }
 
 #[cfg(test)]
@@ -57,4 +103,113 @@ mod tests {
 
         Cli::command().debug_assert();
     }
+
+    #[test]
+    fn test_concurrent_runs_keep_their_own_settings() {
+        use std::fs;
+        use std::sync::{Arc, Barrier};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        // each run loads its templates from its own .v1apprc, the broken
+        // ones fail once files were written
+        for (run, name) in [("ok", "{{ project_name }}"), ("broken", "{{ missing }}")] {
+            let templates = scaffold::test_templates(&root.join(run));
+            fs::write(
+                templates.join("apps/web/src/page.ts.tera"),
+                format!("export const name = \"{}\";\n", name),
+            )
+            .unwrap();
+            fs::write(
+                root.join(run).join(".v1apprc"),
+                "template = \"templates\"\n",
+            )
+            .unwrap();
+        }
+
+        let barrier = Arc::new(Barrier::new(2));
+        let runs: Vec<_> = ["ok", "broken"]
+            .into_iter()
+            .map(|run| {
+                let dir = root.join(run).display().to_string();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    run_with_args(["--yes", "--cwd", &dir, "new", "acme", "--no-git"])
+                })
+            })
+            .collect();
+        let results: Vec<Result<()>> = runs.into_iter().map(|run| run.join().unwrap()).collect();
+
+        assert!(results[0].is_ok(), "{:?}", results[0]);
+        assert!(results[1].is_err());
+        assert_eq!(
+            fs::read_to_string(root.join("ok/acme/apps/web/src/page.ts")).unwrap(),
+            "export const name = \"acme\";\n"
+        );
+        assert!(!root.join("broken/acme").exists());
+    }
+
+    #[test]
+    fn test_json_output_streams_events_and_summary() {
+        use std::cell::RefCell;
+        use std::fs;
+        use std::rc::Rc;
+
+        struct Captured(Rc<RefCell<Vec<u8>>>);
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        scaffold::test_templates(root);
+        fs::write(root.join(".v1apprc"), "template = \"templates\"\n").unwrap();
+        let run = || {
+            let out = Rc::new(RefCell::new(Vec::new()));
+            let cwd = root.display().to_string();
+            let args = ["--cwd", &cwd, "--output", "json", "new", "acme", "--no-git"];
+            let result = run_with_output(args, Box::new(Captured(out.clone())));
+            let out = String::from_utf8(out.take()).unwrap();
+            let events: Vec<serde_json::Value> = out
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            (result, events)
+        };
+
+        let (created, events) = run();
+        // the project is there now
+        let (exists, failed) = run();
+
+        assert!(created.is_ok());
+        assert_eq!(events[0]["event"], "workspace_started");
+        assert!(events.iter().all(|event| event["elapsed_ms"].is_u64()));
+        let written: Vec<&serde_json::Value> = events
+            .iter()
+            .filter(|event| event["event"] == "file_written")
+            .map(|event| &event["path"])
+            .collect();
+        let finished = events.last().unwrap();
+        assert_eq!(finished["event"], "finished");
+        assert_eq!(finished["success"], true);
+        assert_eq!(finished["code"], serde_json::Value::Null);
+        let summary: Vec<&serde_json::Value> = finished["files_written"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .collect();
+        assert_eq!(summary, written);
+
+        assert!(exists.is_err());
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0]["event"], "finished");
+        assert_eq!(failed[0]["success"], false);
+        assert_eq!(failed[0]["code"], "E_PROJECT_EXISTS");
+    }
 }
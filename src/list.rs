@@ -0,0 +1,233 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{fs, path::Path};
+
+use crate::{
+    blueprint::{available_blueprints, Blueprint, WorkspaceKind},
+    cli::Service,
+    error::Error,
+    service::installed_services,
+    utils::print_table,
+};
+
+#[derive(ValueEnum, Clone, Debug, Default)]
+pub enum ListTarget {
+    #[default]
+    #[clap(help = "Every service that can be added")]
+    Services,
+    #[clap(help = "Every provider that can be added")]
+    Providers,
+    #[clap(help = "The services installed in the current project")]
+    Installed,
+    #[clap(help = "The blueprints apps and packages can be added from")]
+    Blueprints,
+}
+
+/// Returns the providers shipped in the templates, sorted by name. Fails
+/// when the templates have no providers directory, rather than listing none.
+pub fn available_providers(templates_root: &Path) -> Result<Vec<String>> {
+    let dir = templates_root.join("providers");
+    let entries =
+        fs::read_dir(&dir).map_err(|_| Error::TemplateNotFound(dir.display().to_string()))?;
+    let mut providers: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().to_str().map(str::to_string))
+        .collect();
+    providers.sort();
+    Ok(providers)
+}
+
+fn names(services: &[Service]) -> Vec<String> {
+    services.iter().map(|s| s.to_string()).collect()
+}
+
+/// A service that can be added, as shown by `list services`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServiceInfo {
+    pub name: Service,
+    pub description: String,
+    pub installed: bool,
+    pub requires: Vec<Service>,
+    pub conflicts: Vec<Service>,
+    pub suggests: Vec<Service>,
+}
+
+impl ServiceInfo {
+    fn new(service: &Service, installed: &[Service]) -> Self {
+        let relations = service.relations();
+        ServiceInfo {
+            name: service.clone(),
+            description: service.description(),
+            installed: installed.contains(service),
+            requires: relations.requires.to_vec(),
+            conflicts: relations.conflicts.to_vec(),
+            suggests: relations.suggests.to_vec(),
+        }
+    }
+}
+
+/// Every service that can be added, marked as installed when its package
+/// exists in `project_dir`.
+pub fn list_services(project_dir: Option<&Path>) -> Vec<ServiceInfo> {
+    let installed = project_dir.map(installed_services).unwrap_or_default();
+    Service::value_variants()
+        .iter()
+        .map(|service| ServiceInfo::new(service, &installed))
+        .collect()
+}
+
+fn listed_services(target: &ListTarget, installed: &[Service]) -> Vec<Service> {
+    match target {
+        ListTarget::Installed => installed.to_vec(),
+        _ => Service::value_variants().to_vec(),
+    }
+}
+
+fn all_blueprints(templates_root: &Path) -> Result<Vec<Blueprint>> {
+    let mut blueprints = available_blueprints(templates_root, WorkspaceKind::App)?;
+    blueprints.extend(available_blueprints(
+        templates_root,
+        WorkspaceKind::Package,
+    )?);
+    Ok(blueprints)
+}
+
+/// What `list --json` prints for `target`.
+pub fn list_json(project_dir: &Path, templates_root: &Path, target: &ListTarget) -> Result<Value> {
+    let value = match target {
+        ListTarget::Services | ListTarget::Installed => {
+            let installed = installed_services(project_dir);
+            let services: Vec<ServiceInfo> = listed_services(target, &installed)
+                .iter()
+                .map(|s| ServiceInfo::new(s, &installed))
+                .collect();
+            serde_json::to_value(services)?
+        }
+        ListTarget::Providers => available_providers(templates_root)?
+            .iter()
+            .map(|p| json!({ "name": p }))
+            .collect(),
+        ListTarget::Blueprints => serde_json::to_value(all_blueprints(templates_root)?)?,
+    };
+    Ok(value)
+}
+
+pub fn run_list(
+    project_dir: &Path,
+    templates_root: &Path,
+    target: ListTarget,
+    as_json: bool,
+) -> Result<()> {
+    if as_json {
+        let value = list_json(project_dir, templates_root, &target)?;
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+
+    match target {
+        ListTarget::Services | ListTarget::Installed => {
+            let installed = installed_services(project_dir);
+            let services = listed_services(&target, &installed);
+            if services.is_empty() {
+                println!("No services installed.");
+            } else {
+                let rows: Vec<[String; 4]> = services
+                    .iter()
+                    .map(|service| {
+                        [
+                            service.to_string(),
+                            service.description(),
+                            if installed.contains(service) {
+                                "yes".to_string()
+                            } else {
+                                String::new()
+                            },
+                            names(service.relations().requires).join(", "),
+                        ]
+                    })
+                    .collect();
+                print_table(&["NAME", "DESCRIPTION", "INSTALLED", "REQUIRES"], &rows);
+            }
+        }
+        ListTarget::Providers => {
+            let providers = available_providers(templates_root)?;
+            if providers.is_empty() {
+                println!("No providers available.");
+            } else {
+                let rows: Vec<[String; 1]> = providers.into_iter().map(|p| [p]).collect();
+                print_table(&["NAME"], &rows);
+            }
+        }
+        ListTarget::Blueprints => {
+            let blueprints = all_blueprints(templates_root)?;
+            if blueprints.is_empty() {
+                println!("No blueprints available.");
+            } else {
+                let rows: Vec<[String; 3]> = blueprints
+                    .into_iter()
+                    .map(|b| [b.name, b.kind.label().to_string(), b.description])
+                    .collect();
+                print_table(&["NAME", "KIND", "DESCRIPTION"], &rows);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_json_marks_installed_services() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path();
+        fs::create_dir_all(project.join("packages/resend")).unwrap();
+
+        let services = list_json(project, project, &ListTarget::Services).unwrap();
+        let installed = list_json(project, project, &ListTarget::Installed).unwrap();
+
+        let services = services.as_array().unwrap();
+        assert_eq!(services.len(), Service::value_variants().len());
+        let resend = services.iter().find(|s| s["name"] == "resend").unwrap();
+        assert_eq!(resend["installed"], true);
+        assert!(resend["description"].is_string());
+        for key in ["requires", "conflicts", "suggests"] {
+            assert!(resend[key].is_array(), "{} is not an array", key);
+        }
+        assert!(services
+            .iter()
+            .filter(|s| s["name"] != "resend")
+            .all(|s| s["installed"] == false));
+        assert_eq!(
+            installed,
+            json!([{
+                "name": "resend",
+                "description": Service::Resend.description(),
+                "installed": true,
+                "requires": [],
+                "conflicts": [],
+                "suggests": [],
+            }])
+        );
+    }
+
+    #[test]
+    fn test_list_json_providers_from_templates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let templates = tmp.path();
+        let missing = list_json(templates, templates, &ListTarget::Providers);
+        fs::create_dir_all(templates.join("providers/vercel")).unwrap();
+        fs::create_dir_all(templates.join("providers/fly")).unwrap();
+        fs::write(templates.join("providers/README.md"), "").unwrap();
+
+        let providers = list_json(templates, templates, &ListTarget::Providers).unwrap();
+
+        assert!(missing.is_err());
+        assert_eq!(providers, json!([{ "name": "fly" }, { "name": "vercel" }]));
+    }
+}
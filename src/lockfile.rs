@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::{blueprint::WorkspaceKind, cli::Service, logger::log_debug};
+
+pub const LOCKFILE_NAME: &str = "create-v1-app.lock.json";
+
+pub const DEFAULT_SCOPE: &str = "v1";
+
+/// What a project was generated from, written to its root so later commands
+/// can work with the same values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// Version of the CLI that generated the project.
+    pub version: String,
+    pub name: String,
+    /// npm scope of the generated packages, without the `@`.
+    #[serde(default = "default_scope")]
+    pub scope: String,
+    pub package_manager: String,
+    #[serde(default)]
+    pub services: Vec<Service>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<String>,
+    /// Extra variables the templates were rendered with.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, serde_json::Value>,
+    /// Apps and packages added from blueprints after the project was
+    /// generated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workspaces: Vec<AddedWorkspace>,
+}
+
+/// A workspace generated with `add app` or `add package`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddedWorkspace {
+    pub kind: WorkspaceKind,
+    pub name: String,
+    pub blueprint: String,
+}
+
+impl AddedWorkspace {
+    /// Relative to the project root, e.g. `apps/docs`.
+    pub fn path(&self) -> String {
+        format!("{}/{}", self.kind.dir(), self.name)
+    }
+}
+
+fn default_scope() -> String {
+    DEFAULT_SCOPE.to_string()
+}
+
+impl Lockfile {
+    pub fn new(name: &str, scope: &str, package_manager: &str) -> Self {
+        Lockfile {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            name: name.to_string(),
+            scope: scope.to_string(),
+            package_manager: package_manager.to_string(),
+            services: Vec::new(),
+            providers: Vec::new(),
+            variables: BTreeMap::new(),
+            workspaces: Vec::new(),
+        }
+    }
+}
+
+/// The npm package name of a workspace, e.g. `@v1/resend`.
+pub fn scoped_package_name(scope: &str, workspace: &str) -> String {
+    format!("@{}/{}", scope, workspace)
+}
+
+pub fn read_lockfile(project_dir: &Path) -> Result<Option<Lockfile>> {
+    let path = project_dir.join(LOCKFILE_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let lockfile =
+        serde_json::from_str(&content).map_err(|e| anyhow!("Invalid {}: {}", path.display(), e))?;
+    Ok(Some(lockfile))
+}
+
+pub fn write_lockfile(project_dir: &Path, lockfile: &Lockfile) -> Result<()> {
+    let path = project_dir.join(LOCKFILE_NAME);
+    let content = serde_json::to_string_pretty(lockfile)?;
+    fs::write(&path, format!("{}\n", content))
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    log_debug(&format!("Updated {}", path.display()));
+    Ok(())
+}
+
+/// Applies `update` to the project's lockfile, if it has one.
+pub fn update_lockfile(project_dir: &Path, update: impl FnOnce(&mut Lockfile)) -> Result<()> {
+    if let Some(mut lockfile) = read_lockfile(project_dir)? {
+        update(&mut lockfile);
+        write_lockfile(project_dir, &lockfile)?;
+    }
+    Ok(())
+}
+
+/// The scope recorded in the project's lockfile, `v1` for projects without one.
+pub fn project_scope(project_dir: &Path) -> Result<String> {
+    Ok(read_lockfile(project_dir)?
+        .map(|lockfile| lockfile.scope)
+        .unwrap_or_else(default_scope))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockfile_defaults_to_v1_scope() {
+        let lockfile: Lockfile = serde_json::from_str(
+            r#"{ "version": "0.1.0", "name": "acme", "package_manager": "bun" }"#,
+        )
+        .unwrap();
+        assert_eq!(lockfile.scope, "v1");
+        assert_eq!(scoped_package_name(&lockfile.scope, "ui"), "@v1/ui");
+        assert!(lockfile.services.is_empty());
+    }
+}
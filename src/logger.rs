@@ -1,53 +1,266 @@
 use anyhow::Result;
-use console::{style, Emoji};
+use console::{style, Term};
 use log::{Level, LevelFilter, Metadata, Record};
+use std::{
+    cell::RefCell,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Number of run logs kept in the log directory.
+const MAX_LOG_FILES: usize = 20;
+/// A run log stops growing past this size.
+const MAX_LOG_SIZE: u64 = 10 * 1024 * 1024;
+/// What is shown on the console outside of a run, or until its flags are read.
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
 
 struct ColoredLogger;
 
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+}
+
+/// The log of one run: the level it shows on the console and the file that
+/// gets everything it logs.
+struct RunLog {
+    console_level: LevelFilter,
+    file: Option<LogFile>,
+    start: Instant,
+}
+
+/// Tells apart the logs of runs started in the same millisecond.
+static RUN_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // per thread, so runs sharing a process each log to their own file
+    static RUN_LOG: RefCell<Option<RunLog>> = const { RefCell::new(None) };
+    static LAST_LOG_FILE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Colors and emojis are only used when stderr is a color terminal, which
+/// also rules out `NO_COLOR` and redirected output.
+fn is_styled() -> bool {
+    console::colors_enabled_stderr() && Term::stderr().features().wants_emoji()
+}
+
+fn console_level() -> LevelFilter {
+    RUN_LOG
+        .try_with(|run_log| {
+            run_log
+                .try_borrow()
+                .ok()
+                .and_then(|run_log| run_log.as_ref().map(|run_log| run_log.console_level))
+        })
+        .ok()
+        .flatten()
+        .unwrap_or(DEFAULT_LEVEL)
+}
+
+fn console_enabled(level: Level) -> bool {
+    level <= console_level()
+}
+
 impl log::Log for ColoredLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= Level::Debug
+        // the log file gets everything this crate logs
+        metadata.target().starts_with(env!("CARGO_CRATE_NAME")) || console_enabled(metadata.level())
     }
 
     fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            let level_str = match record.level() {
-                Level::Error => {
-                    format!("{}{}", Emoji("🚨 ", ""), style("ERROR").red().bold().dim())
-                }
-                Level::Warn => format!(
-                    "{}{}",
-                    Emoji("🚧 ", ""),
-                    style("WARN ").yellow().bold().dim()
-                ),
-                Level::Info => format!(
-                    "{}{}",
-                    Emoji("🚀 ", ""),
-                    style("INFO ").green().bold().dim()
-                ),
-                Level::Debug => {
-                    format!("{}{}", Emoji("🔍 ", ""), style("DEBUG").blue().bold().dim())
-                }
-                Level::Trace => format!(
-                    "{}{}",
-                    Emoji("🔎 ", ""),
-                    style("TRACE").magenta().bold().dim()
-                ),
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if record.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            write_to_file(record);
+        }
+
+        if console_enabled(record.level()) {
+            let level_str = if is_styled() {
+                let (emoji, level) = match record.level() {
+                    Level::Error => ("🚨 ", style("ERROR").red()),
+                    Level::Warn => ("🚧 ", style("WARN ").yellow()),
+                    Level::Info => ("🚀 ", style("INFO ").green()),
+                    Level::Debug => ("🔍 ", style("DEBUG").blue()),
+                    Level::Trace => ("🔎 ", style("TRACE").magenta()),
+                };
+                format!("{}{}", emoji, level.for_stderr().bold().dim())
+            } else {
+                format!("{:<5}", record.level())
             };
 
-            println!("{} - {}", level_str, record.args());
+            eprintln!("{} - {}", level_str, record.args());
         }
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        let _ = RUN_LOG.try_with(|run_log| {
+            if let Ok(mut run_log) = run_log.try_borrow_mut() {
+                if let Some(log_file) = run_log.as_mut().and_then(|run_log| run_log.file.as_mut()) {
+                    let _ = log_file.file.flush();
+                }
+            }
+        });
+    }
+}
+
+/// Appends the record to the log of the run on this thread, if there is one.
+fn write_to_file(record: &Record) {
+    let _ = RUN_LOG.try_with(|run_log| {
+        let Ok(mut run_log) = run_log.try_borrow_mut() else {
+            return;
+        };
+        let Some(run_log) = run_log.as_mut() else {
+            return;
+        };
+        let elapsed = run_log.start.elapsed();
+        let Some(log_file) = run_log.file.as_mut() else {
+            return;
+        };
+        if log_file.written > MAX_LOG_SIZE {
+            return;
+        }
+
+        let mut line = format!(
+            "[{:>4}.{:03}s] {:<5} - {}\n",
+            elapsed.as_secs(),
+            elapsed.subsec_millis(),
+            record.level(),
+            record.args()
+        );
+        log_file.written += line.len() as u64;
+        if log_file.written > MAX_LOG_SIZE {
+            line.push_str("log truncated, it reached its maximum size\n");
+        }
+        let _ = log_file.file.write_all(line.as_bytes());
+    });
 }
 
 static LOGGER: ColoredLogger = ColoredLogger;
 
-pub fn initialize_logger() -> Result<()> {
-    log::set_logger(&LOGGER)
-        .map(|()| log::set_max_level(LevelFilter::Debug))
-        .map_err(|e| anyhow::anyhow!(e))
+/// Runs `f` as one run of the CLI started with `args`, the program name
+/// included. Everything logged on this thread meanwhile goes to a new run
+/// log, and is shown at info level until `set_log_level` is called with the
+/// level asked for on the command line.
+pub fn with_run_log<T>(args: &[String], f: impl FnOnce() -> T) -> T {
+    // only the first call installs the logger
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Trace);
+    }
+
+    let (file, error) = match log_dir() {
+        Some(dir) => match open_log_file(&dir, args) {
+            Ok(log_file) => (Some(log_file), None),
+            Err(e) => (None, Some((dir, e))),
+        },
+        None => (None, None),
+    };
+    let path = file.as_ref().map(|log_file| log_file.path.clone());
+    let run_log = RunLog {
+        console_level: DEFAULT_LEVEL,
+        file,
+        start: Instant::now(),
+    };
+
+    let previous = RUN_LOG.with(|current| current.borrow_mut().replace(run_log));
+    if let Some((dir, e)) = error {
+        log_warn(&format!(
+            "Could not create a log file in {}: {}",
+            dir.display(),
+            e
+        ));
+    }
+    let result = f();
+    RUN_LOG.with(|current| *current.borrow_mut() = previous);
+    LAST_LOG_FILE.with(|last| *last.borrow_mut() = path);
+    result
+}
+
+/// Sets what the run on this thread shows on the console.
+pub fn set_log_level(level: LevelFilter) {
+    RUN_LOG.with(|run_log| {
+        if let Some(run_log) = run_log.borrow_mut().as_mut() {
+            run_log.console_level = level;
+        }
+    });
+}
+
+/// `create-v1-app/logs` in the OS cache dir.
+pub fn log_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("create-v1-app").join("logs"))
+}
+
+/// The log of the last run on this thread, if it could be created.
+pub fn log_file_path() -> Option<PathBuf> {
+    LAST_LOG_FILE.with(|last| last.borrow().clone())
+}
+
+/// Starts a new log for the run of `args`, dropping the oldest ones past
+/// `MAX_LOG_FILES`.
+fn open_log_file(dir: &Path, args: &[String]) -> Result<LogFile> {
+    fs::create_dir_all(dir)?;
+    prune_logs(dir, MAX_LOG_FILES - 1)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let path = dir.join(format!(
+        "run-{}-{}-{}.log",
+        timestamp,
+        std::process::id(),
+        RUN_COUNT.fetch_add(1, Ordering::SeqCst)
+    ));
+    let mut file = File::create(&path)?;
+    writeln!(
+        file,
+        "create-v1-app {} - {}",
+        env!("CARGO_PKG_VERSION"),
+        args.join(" ")
+    )?;
+
+    Ok(LogFile {
+        path,
+        file,
+        written: 0,
+    })
+}
+
+/// Removes the oldest `run-*.log` files so that at most `keep` remain.
+fn prune_logs(dir: &Path, keep: usize) -> Result<()> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("run-") && name.ends_with(".log"))
+        })
+        .collect();
+
+    // names start with the timestamp, so they sort oldest first
+    logs.sort_by_key(|path| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.trim_start_matches("run-").split('-').next())
+            .and_then(|timestamp| timestamp.parse::<u128>().ok())
+            .unwrap_or_default()
+    });
+
+    let excess = logs.len().saturating_sub(keep);
+    for path in logs.into_iter().take(excess) {
+        // a concurrent run may have pruned it already
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
 }
 
 #[allow(dead_code)]
@@ -71,3 +284,75 @@ pub fn log_debug(message: &str) {
 pub fn log_trace(message: &str) {
     log::trace!("{}", message);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prune_keeps_newest_logs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for timestamp in [300, 1000, 20, 4000] {
+            fs::write(dir.join(format!("run-{}-1.log", timestamp)), "").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        prune_logs(dir, 2).unwrap();
+        let mut left: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        left.sort();
+
+        assert_eq!(left, ["notes.txt", "run-1000-1.log", "run-4000-1.log"]);
+    }
+
+    #[test]
+    fn test_each_run_logs_to_its_own_file() {
+        let runs: Vec<_> = ["one", "two"]
+            .into_iter()
+            .map(|name| {
+                std::thread::spawn(move || {
+                    let args = ["create-v1-app", "new", name].map(String::from);
+                    with_run_log(&args, || log_debug(&format!("Generating {}", name)));
+                    log_file_path()
+                })
+            })
+            .collect();
+
+        for (run, name) in runs.into_iter().zip(["one", "two"]) {
+            // no log is written without a cache dir
+            let Some(path) = run.join().unwrap() else {
+                continue;
+            };
+            let log = fs::read_to_string(&path).unwrap();
+            let _ = fs::remove_file(&path);
+
+            let header = format!(
+                "create-v1-app {} - create-v1-app new {}",
+                env!("CARGO_PKG_VERSION"),
+                name
+            );
+            assert_eq!(log.lines().next(), Some(header.as_str()));
+            assert_eq!(log.lines().count(), 2);
+            assert!(log.contains(&format!("Generating {}", name)));
+        }
+    }
+
+    #[test]
+    fn test_log_level_holds_for_its_run() {
+        let args = ["create-v1-app", "-q"].map(String::from);
+        let during = with_run_log(&args, || {
+            set_log_level(LevelFilter::Warn);
+            (console_enabled(Level::Info), console_enabled(Level::Warn))
+        });
+        if let Some(path) = log_file_path() {
+            let _ = fs::remove_file(path);
+        }
+
+        assert_eq!(during, (false, true));
+        assert!(console_enabled(Level::Info));
+        assert!(!console_enabled(Level::Debug));
+    }
+}
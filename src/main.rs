@@ -1,11 +1,24 @@
 use anyhow::Result;
-use create_v1_app::{run, set_cleanup_needed};
+use create_v1_app::{cancel, error_code, exit_code, log_file_path, run};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 fn main() -> Result<()> {
+    let cancelling = AtomicBool::new(false);
+    ctrlc::set_handler(move || {
+        // a second ctrl+c doesn't wait for the rollback
+        if cancelling.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        eprintln!("Cancelling, press Ctrl-C again to exit immediately");
+        cancel();
+    })?;
+
     if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        set_cleanup_needed();
-        std::process::exit(1);
+        eprintln!("Error [{}]: {}", error_code(&e), e);
+        if let Some(path) = log_file_path() {
+            eprintln!("A full log of this run is at {}", path.display());
+        }
+        std::process::exit(exit_code(&e));
     }
     Ok(())
 }
@@ -0,0 +1,303 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{error::Error, logger::log_debug};
+
+pub const PACKAGE_MANAGERS: [&str; 4] = ["npm", "yarn", "pnpm", "bun"];
+
+/// The `workspaces` of a package.json, a list of patterns or yarn's object
+/// with the patterns next to `nohoist`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Workspaces {
+    Patterns(Vec<String>),
+    Object {
+        #[serde(default)]
+        packages: Vec<String>,
+        #[serde(flatten)]
+        other: Map<String, Value>,
+    },
+}
+
+impl Default for Workspaces {
+    fn default() -> Self {
+        Workspaces::Patterns(Vec::new())
+    }
+}
+
+impl Workspaces {
+    pub fn patterns(&self) -> &[String] {
+        match self {
+            Workspaces::Patterns(patterns) => patterns,
+            Workspaces::Object { packages, .. } => packages,
+        }
+    }
+
+    pub fn patterns_mut(&mut self) -> &mut Vec<String> {
+        match self {
+            Workspaces::Patterns(patterns) => patterns,
+            Workspaces::Object { packages, .. } => packages,
+        }
+    }
+}
+
+/// The parts of a package.json the CLI reads or edits. Every other field is
+/// kept as is when the file is written back, and so is the order of the keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageJson {
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// The file as it was read, which edits are written back into.
+    #[serde(skip)]
+    pub source: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package_manager: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspaces: Option<Workspaces>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scripts: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dev_dependencies: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub peer_dependencies: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub optional_dependencies: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl PackageJson {
+    /// Reads the package.json in `dir`.
+    pub fn read(dir: &Path) -> Result<PackageJson> {
+        let path = dir.join("package.json");
+        let invalid = |message: String| Error::InvalidPackageJson {
+            path: path.clone(),
+            message,
+        };
+        if !path.is_file() {
+            return Err(invalid("the file does not exist".to_string()).into());
+        }
+
+        let content = fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+        let mut package_json = PackageJson::parse(&content).map_err(|e| invalid(e.to_string()))?;
+        package_json.path = path;
+        Ok(package_json)
+    }
+
+    /// Parses package.json content, naming the offending field on errors.
+    pub fn parse(content: &str) -> Result<PackageJson> {
+        let deserializer = &mut serde_json::Deserializer::from_str(content);
+        let mut package_json: PackageJson = serde_path_to_error::deserialize(deserializer)
+            .map_err(|e| {
+                let field = e.path().to_string();
+                if field == "." {
+                    anyhow!("{}", e.inner())
+                } else {
+                    anyhow!("`{}`: {}", field, e.inner())
+                }
+            })?;
+        package_json.source = Some(serde_json::from_str(content)?);
+        Ok(package_json)
+    }
+
+    /// The content to write, the fields in the order they were read in and
+    /// new ones last.
+    pub fn to_value(&self) -> Result<Value> {
+        let value = serde_json::to_value(self)?;
+        Ok(match &self.source {
+            Some(source) => merge_in_order(source.clone(), value),
+            None => value,
+        })
+    }
+
+    pub fn write(&self) -> Result<()> {
+        let content = serde_json::to_string_pretty(&self.to_value()?)?;
+        fs::write(&self.path, format!("{}\n", content))
+            .map_err(|e| anyhow!("Failed to write {}: {}", self.path.display(), e))?;
+        log_debug(&format!("Updated {}", self.path.display()));
+        Ok(())
+    }
+
+    fn invalid(&self, message: String) -> Error {
+        Error::InvalidPackageJson {
+            path: self.path.clone(),
+            message,
+        }
+    }
+
+    pub fn name(&self) -> Result<&str> {
+        Ok(self
+            .name
+            .as_deref()
+            .ok_or_else(|| self.invalid("the `name` field is missing".to_string()))?)
+    }
+
+    /// The package manager without its version, e.g. `bun` for `bun@1.1.30`.
+    pub fn package_manager(&self) -> Result<&str> {
+        let value = self.package_manager.as_deref().ok_or_else(|| {
+            self.invalid(
+                "the `packageManager` field is missing, add one such as \"packageManager\": \"npm@10.8.2\""
+                    .to_string(),
+            )
+        })?;
+
+        let name = value.split('@').next().unwrap_or_default();
+        if !PACKAGE_MANAGERS.contains(&name) {
+            return Err(self
+                .invalid(format!(
+                    "unsupported `packageManager` {}, expected one of {}",
+                    value,
+                    PACKAGE_MANAGERS.join(", ")
+                ))
+                .into());
+        }
+        Ok(name)
+    }
+
+    /// Removes the package from every dependency list, returning whether it
+    /// was listed anywhere.
+    pub fn remove_dependency(&mut self, package: &str) -> bool {
+        let mut removed = false;
+        for deps in [
+            &mut self.dependencies,
+            &mut self.dev_dependencies,
+            &mut self.peer_dependencies,
+            &mut self.optional_dependencies,
+        ] {
+            removed |= deps.remove(package).is_some();
+        }
+        removed
+    }
+}
+
+/// `updated`, its objects keeping the key order of `source`.
+fn merge_in_order(source: Value, updated: Value) -> Value {
+    match (source, updated) {
+        (Value::Object(mut source), Value::Object(mut updated)) => {
+            source.retain(|key, _| updated.contains_key(key));
+            for (key, value) in source.iter_mut() {
+                if let Some(new) = updated.shift_remove(key) {
+                    *value = merge_in_order(value.take(), new);
+                }
+            }
+            source.extend(updated);
+            Value::Object(source)
+        }
+        (_, updated) => updated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_package_manager_is_an_error() {
+        let package_json = PackageJson::parse(r#"{ "name": "acme" }"#).unwrap();
+        assert_eq!(package_json.name().unwrap(), "acme");
+        assert!(package_json
+            .package_manager()
+            .unwrap_err()
+            .to_string()
+            .contains("`packageManager`"));
+    }
+
+    #[test]
+    fn test_invalid_field_is_named() {
+        let err = PackageJson::parse(r#"{ "name": "acme", "workspaces": "apps/*" }"#).unwrap_err();
+        assert!(err.to_string().starts_with("`workspaces`"));
+
+        let err = PackageJson::parse(r#"{ "scripts": { "dev": 1 } }"#).unwrap_err();
+        assert!(err.to_string().starts_with("`scripts.dev`"));
+    }
+
+    #[test]
+    fn test_round_trip_keeps_other_fields() {
+        let mut package_json = PackageJson::parse(
+            r#"{
+                "name": "acme",
+                "private": true,
+                "packageManager": "bun@1.1.30",
+                "dependencies": { "@v1/resend": "workspace:*", "react": "^19.0.0" }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(package_json.package_manager().unwrap(), "bun");
+        assert!(package_json.remove_dependency("@v1/resend"));
+        assert!(!package_json.remove_dependency("@v1/resend"));
+
+        let value = serde_json::to_value(&package_json).unwrap();
+        assert_eq!(value["private"], true);
+        assert_eq!(value["dependencies"]["react"], "^19.0.0");
+        assert!(value["dependencies"].get("@v1/resend").is_none());
+    }
+
+    #[test]
+    fn test_write_keeps_key_order() {
+        let content = r#"{
+  "name": "acme",
+  "private": true,
+  "workspaces": {
+    "packages": ["apps/*"],
+    "nohoist": ["**/react-native"]
+  },
+  "scripts": {
+    "dev": "turbo dev",
+    "build": "turbo build"
+  },
+  "dependencies": {
+    "react": "^19.0.0",
+    "@v1/resend": "workspace:*"
+  }
+}"#;
+        let mut package_json = PackageJson::parse(content).unwrap();
+        package_json
+            .workspaces
+            .as_mut()
+            .unwrap()
+            .patterns_mut()
+            .push("packages/*".to_string());
+        package_json.remove_dependency("@v1/resend");
+        package_json
+            .scripts
+            .insert("lint".to_string(), "biome lint".to_string());
+
+        let written = serde_json::to_string_pretty(&package_json.to_value().unwrap()).unwrap();
+        assert_eq!(
+            written,
+            r#"{
+  "name": "acme",
+  "private": true,
+  "workspaces": {
+    "packages": [
+      "apps/*",
+      "packages/*"
+    ],
+    "nohoist": [
+      "**/react-native"
+    ]
+  },
+  "scripts": {
+    "dev": "turbo dev",
+    "build": "turbo build",
+    "lint": "biome lint"
+  },
+  "dependencies": {
+    "react": "^19.0.0"
+  }
+}"#
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    blueprint::{generate_workspace, WorkspaceKind},
+    cleanup::{CleanupManager, CleanupTask},
+    cli::Service,
+    resolver::{resolve_services, ServicePlan},
+    service::{self, generate_services, installed_services},
+    utils::find_project_root,
+};
+
+/// Adds services and the ones they require to the project containing
+/// `project_dir` from the templates in `templates_root`, without prompting.
+/// Returns the services added, the installed ones are skipped. Everything
+/// written is removed again when it fails.
+pub fn add_services(
+    project_dir: &Path,
+    templates_root: &Path,
+    services: &[Service],
+) -> Result<Vec<Service>> {
+    add_services_with(project_dir, templates_root, services, |_| Ok(true))
+}
+
+/// `add_services`, going ahead only once `confirm` accepts the resolved
+/// plan. Nothing is added when it doesn't.
+pub fn add_services_with(
+    project_dir: &Path,
+    templates_root: &Path,
+    services: &[Service],
+    confirm: impl FnOnce(&ServicePlan) -> Result<bool>,
+) -> Result<Vec<Service>> {
+    let project_dir = find_project_root(project_dir)?;
+    let plan = resolve_services(services, &installed_services(&project_dir))?;
+    if !confirm(&plan)? {
+        return Ok(Vec::new());
+    }
+    let services = plan.services();
+    if services.is_empty() {
+        return Ok(services);
+    }
+
+    let mut rollback = CleanupManager::new();
+    for service in &services {
+        rollback.add_task(CleanupTask::RemoveService {
+            project_dir: project_dir.clone(),
+            service_name: service.to_string(),
+        });
+    }
+
+    if let Err(e) = generate_services(&project_dir, templates_root, &services) {
+        rollback.cleanup();
+        return Err(e);
+    }
+    Ok(services)
+}
+
+/// Removes installed services from the project containing `project_dir`,
+/// with their imports and environment variables.
+pub fn remove_services(project_dir: &Path, services: &[Service]) -> Result<()> {
+    service::remove_services(&find_project_root(project_dir)?, services)
+}
+
+/// Generates a new app or package named `name` from a blueprint of the
+/// templates in `templates_root` in the project containing `project_dir`
+/// and registers it as a workspace. Returns its directory, which is removed
+/// again when it fails.
+pub fn add_workspace(
+    project_dir: &Path,
+    templates_root: &Path,
+    kind: WorkspaceKind,
+    name: &str,
+    blueprint: &str,
+) -> Result<PathBuf> {
+    let project_dir = find_project_root(project_dir)?;
+    let workspace_dir = project_dir.join(kind.dir()).join(name);
+    let existed = workspace_dir.exists();
+
+    let result = generate_workspace(&project_dir, templates_root, kind, name, blueprint);
+    if result.is_err() && !existed {
+        let mut rollback = CleanupManager::new();
+        rollback.add_task(CleanupTask::RemoveDirectory(workspace_dir.clone()));
+        rollback.cleanup();
+    }
+    result?;
+    Ok(workspace_dir)
+}
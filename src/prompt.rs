@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+use std::io::IsTerminal;
+
+use crate::{error::Error, logger::log_debug};
+
+/// Asks the questions of one run. A non-interactive one takes the default
+/// of every prompt instead, or fails when it has none.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Prompter {
+    non_interactive: bool,
+}
+
+impl Prompter {
+    pub fn new(non_interactive: bool) -> Self {
+        Prompter { non_interactive }
+    }
+
+    /// Prompts are shown only when not disabled and stdin is a terminal.
+    pub fn is_interactive(&self) -> bool {
+        !self.non_interactive && std::io::stdin().is_terminal()
+    }
+
+    pub fn confirm(&self, prompt: &str, default: bool) -> Result<bool> {
+        if !self.is_interactive() {
+            log_debug(&format!("{} -> {} (non-interactive)", prompt, default));
+            return Ok(default);
+        }
+
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(default)
+            .interact()
+            .map_err(|e| anyhow!(e.to_string()))
+    }
+
+    pub fn select<T: ToString>(&self, prompt: &str, items: &[T], default: usize) -> Result<usize> {
+        if !self.is_interactive() {
+            log_debug(&format!(
+                "{} -> {} (non-interactive)",
+                prompt,
+                items[default].to_string()
+            ));
+            return Ok(default);
+        }
+
+        Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(items)
+            .default(default)
+            .interact()
+            .map_err(|e| anyhow!(e.to_string()))
+    }
+
+    /// Selects the items checked by default when not interactive.
+    pub fn multi_select<T: ToString>(
+        &self,
+        prompt: &str,
+        items: &[T],
+        defaults: &[bool],
+    ) -> Result<Vec<usize>> {
+        if !self.is_interactive() {
+            let selected: Vec<usize> = (0..items.len())
+                .filter(|&i| defaults.get(i).copied().unwrap_or(false))
+                .collect();
+            log_debug(&format!("{} -> {:?} (non-interactive)", prompt, selected));
+            return Ok(selected);
+        }
+
+        MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(items)
+            .defaults(defaults)
+            .interact()
+            .map_err(|e| anyhow!(e.to_string()))
+    }
+
+    pub fn input<V>(
+        &self,
+        prompt: &str,
+        default: Option<&str>,
+        allow_empty: bool,
+        validate: V,
+    ) -> Result<String>
+    where
+        V: Fn(&str) -> Result<()>,
+    {
+        if !self.is_interactive() {
+            return match default {
+                Some(value) => {
+                    validate(value)?;
+                    log_debug(&format!("{} -> {} (non-interactive)", prompt, value));
+                    Ok(value.to_string())
+                }
+                None if allow_empty => Ok(String::new()),
+                None => Err(Error::MissingAnswer(format!(
+                    "\"{}\" needs an answer but prompts are disabled, pass it as an argument or in an answers file",
+                    prompt
+                ))
+                .into()),
+            };
+        }
+
+        let theme = ColorfulTheme::default();
+        let mut input = Input::<String>::with_theme(&theme)
+            .with_prompt(prompt)
+            .allow_empty(allow_empty)
+            .validate_with(|value: &String| validate(value).map_err(|e| e.to_string()));
+        if let Some(default) = default {
+            input = input.default(default.to_string());
+        }
+        input.interact_text().map_err(|e| anyhow!(e.to_string()))
+    }
+}
@@ -0,0 +1,430 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use console::style;
+use regex::{NoExpand, Regex};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cli::Service,
+    diff::{print_diff, unified_diff},
+    env::parse_env,
+    error::invalid_input,
+    imports::rename_module_specifiers,
+    lockfile::{read_lockfile, DEFAULT_SCOPE, LOCKFILE_NAME},
+    logger::{log_debug, log_info, log_warn},
+    package_json::PackageJson,
+    prompt::Prompter,
+    utils::{is_source_file, is_valid_project_name, is_valid_scope, project_files},
+};
+
+/// A file a rename rewrites.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub before: String,
+    pub after: String,
+}
+
+/// Everything a rename changes, worked out before anything is written.
+#[derive(Debug, Clone)]
+pub struct RenamePlan {
+    pub changes: Vec<FileChange>,
+    /// `file:line` of source lines still naming the old scope outside an
+    /// import, which are left for a person to look at.
+    pub unrenamed: Vec<String>,
+}
+
+/// The old and new values of a rename, the old ones as recorded when the
+/// project was generated.
+struct Rename {
+    old_name: String,
+    new_name: String,
+    old_scope: String,
+    new_scope: String,
+}
+
+impl Rename {
+    fn renames_name(&self) -> bool {
+        self.old_name != self.new_name
+    }
+
+    fn renames_scope(&self) -> bool {
+        self.old_scope != self.new_scope
+    }
+
+    /// `@v1/ui/button` becomes `@acme/ui/button`.
+    fn rename_package(&self, package: &str) -> Option<String> {
+        package
+            .strip_prefix(&format!("@{}/", self.old_scope))
+            .map(|rest| format!("@{}/{}", self.new_scope, rest))
+    }
+}
+
+/// Environment variables of a project are prefixed with its name, e.g.
+/// `ACME_API_KEY` in `acme`.
+fn env_prefix(name: &str) -> String {
+    format!("{}_", name.to_uppercase().replace('-', "_"))
+}
+
+fn is_env_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(".env"))
+}
+
+/// Works out every change renaming the project containing `project_dir` to
+/// `name` and/or moving its packages to `scope` makes, without writing any.
+pub fn plan_rename(
+    project_dir: &Path,
+    name: Option<&str>,
+    scope: Option<&str>,
+) -> Result<RenamePlan> {
+    if name.is_none() && scope.is_none() {
+        return Err(invalid_input("Pass a new name, a new --scope or both"));
+    }
+
+    let lockfile = read_lockfile(project_dir)?;
+    let package_json = PackageJson::read(project_dir)?;
+    let old_name = match &lockfile {
+        Some(lockfile) => lockfile.name.clone(),
+        None => package_json.name()?.to_string(),
+    };
+    let old_scope = match &lockfile {
+        Some(lockfile) => lockfile.scope.clone(),
+        None => DEFAULT_SCOPE.to_string(),
+    };
+
+    let rename = Rename {
+        new_name: name.unwrap_or(&old_name).to_string(),
+        new_scope: scope
+            .map(|scope| scope.trim_start_matches('@').to_string())
+            .unwrap_or_else(|| old_scope.clone()),
+        old_name,
+        old_scope,
+    };
+    is_valid_project_name(&rename.new_name)?;
+    is_valid_scope(&rename.new_scope)?;
+    if !rename.renames_name() && !rename.renames_scope() {
+        return Err(invalid_input(format!(
+            "The project is already named {} with the @{} scope",
+            rename.old_name, rename.old_scope
+        )));
+    }
+
+    let files: Vec<PathBuf> = project_files(project_dir)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| !path.ends_with(LOCKFILE_NAME))
+        .collect();
+    let env_names = if rename.renames_name() {
+        prefixed_env_names(&files, &env_prefix(&rename.old_name))
+    } else {
+        BTreeSet::new()
+    };
+
+    let old_scope = format!("@{}/", rename.old_scope);
+    let mut changes = Vec::new();
+    let mut unrenamed = Vec::new();
+    for path in files {
+        let Ok(before) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let after = rewrite_file(&path, project_dir, &before, &rename, &env_names)?;
+
+        if rename.renames_scope() && is_source_file(&path) {
+            let relative = path.strip_prefix(project_dir).unwrap_or(&path);
+            unrenamed.extend(
+                after
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| line.contains(&old_scope))
+                    .map(|(index, _)| format!("{}:{}", relative.display(), index + 1)),
+            );
+        }
+        if after != before {
+            changes.push(FileChange {
+                path,
+                before,
+                after,
+            });
+        }
+    }
+
+    if let Some(mut lockfile) = lockfile {
+        let path = project_dir.join(LOCKFILE_NAME);
+        let before = fs::read_to_string(&path)?;
+        lockfile.name = rename.new_name.clone();
+        lockfile.scope = rename.new_scope.clone();
+        let after = format!("{}\n", serde_json::to_string_pretty(&lockfile)?);
+        changes.push(FileChange {
+            path,
+            before,
+            after,
+        });
+    }
+
+    Ok(RenamePlan { changes, unrenamed })
+}
+
+/// The names of the environment variables declared with the project's
+/// prefix in any `.env*` file. The services' variables keep their names
+/// whatever the project is called, e.g. `NEXT_PUBLIC_SENTRY_DSN` in `next`.
+fn prefixed_env_names(files: &[PathBuf], prefix: &str) -> BTreeSet<String> {
+    let service_names: BTreeSet<&str> = Service::value_variants()
+        .iter()
+        .flat_map(|service| service.env_vars())
+        .map(|var| var.name)
+        .collect();
+    files
+        .iter()
+        .filter(|path| is_env_file(path))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|content| parse_env(&content).into_keys())
+        .filter(|name| name.starts_with(prefix) && !service_names.contains(name.as_str()))
+        .collect()
+}
+
+fn rewrite_file(
+    path: &Path,
+    project_dir: &Path,
+    content: &str,
+    rename: &Rename,
+    env_names: &BTreeSet<String>,
+) -> Result<String> {
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let is_json = path.extension().is_some_and(|ext| ext == "json");
+
+    let mut content = content.to_string();
+    if rename.renames_scope() {
+        if is_source_file(path) {
+            match rename_module_specifiers(path, &content, |s| rename.rename_package(s)) {
+                Ok((renamed, count)) => {
+                    log_debug(&format!("{} imports renamed in {}", count, path.display()));
+                    content = renamed;
+                }
+                Err(e) => log_warn(&format!("{}, its imports are left as they are", e)),
+            }
+        } else if is_json {
+            // names, dependencies and scripts such as `--filter=@v1/web`
+            let scope = Regex::new(&format!(
+                r"(^|[^\w.@-])@{}/",
+                regex::escape(&rename.old_scope)
+            ))?;
+            content = scope
+                .replace_all(&content, format!("${{1}}@{}/", rename.new_scope))
+                .to_string();
+        }
+    }
+
+    if rename.renames_name() {
+        if path == project_dir.join("package.json") {
+            let root_name = Regex::new(&format!(
+                r#""name"(\s*):(\s*)"{}""#,
+                regex::escape(&rename.old_name)
+            ))?;
+            content = root_name
+                .replacen(
+                    &content,
+                    1,
+                    format!(r#""name"${{1}}:${{2}}"{}""#, rename.new_name),
+                )
+                .to_string();
+        }
+
+        // only the titles, the name may well be a plain word elsewhere
+        if file_name.eq_ignore_ascii_case("README.md") {
+            let title = Regex::new(&format!(r"\b{}\b", regex::escape(&rename.old_name)))?;
+            content = content
+                .split_inclusive('\n')
+                .map(|line| match line.starts_with('#') {
+                    true => title.replace_all(line, NoExpand(&rename.new_name)),
+                    false => line.into(),
+                })
+                .collect();
+        }
+
+        if !env_names.is_empty() && (is_env_file(path) || is_source_file(path) || is_json) {
+            let old_prefix = env_prefix(&rename.old_name);
+            let suffixes: Vec<String> = env_names
+                .iter()
+                .map(|name| regex::escape(&name[old_prefix.len()..]))
+                .collect();
+            let env = Regex::new(&format!(
+                r"\b{}({})\b",
+                regex::escape(&old_prefix),
+                suffixes.join("|")
+            ))?;
+            content = env
+                .replace_all(&content, format!("{}${{1}}", env_prefix(&rename.new_name)))
+                .to_string();
+        }
+    }
+
+    Ok(content)
+}
+
+/// Writes the changes, restoring the files already written when one fails.
+pub fn apply_changes(changes: &[FileChange]) -> Result<()> {
+    for (index, change) in changes.iter().enumerate() {
+        if let Err(e) = fs::write(&change.path, &change.after) {
+            for written in &changes[..index] {
+                let _ = fs::write(&written.path, &written.before);
+            }
+            return Err(anyhow!("Failed to write {}: {}", change.path.display(), e));
+        }
+        log_debug(&format!("Updated {}", change.path.display()));
+    }
+    Ok(())
+}
+
+/// Previews the rename as diffs and applies it once confirmed.
+pub fn run_rename(
+    prompter: &Prompter,
+    project_dir: &Path,
+    name: Option<&str>,
+    scope: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    let plan = plan_rename(project_dir, name, scope)?;
+    for change in &plan.changes {
+        let relative = change
+            .path
+            .strip_prefix(project_dir)
+            .unwrap_or(&change.path);
+        print_diff(&unified_diff(
+            relative,
+            change.before.as_bytes(),
+            Some(change.after.as_bytes()),
+        ));
+    }
+    println!(
+        "{}",
+        style(format!("{} file(s) will be changed", plan.changes.len())).bold()
+    );
+    for line in &plan.unrenamed {
+        log_warn(&format!(
+            "{} still names the old scope outside an import, rename it by hand",
+            line
+        ));
+    }
+
+    if dry_run || !prompter.confirm("Apply these changes?", true)? {
+        log_info("Nothing was changed");
+        return Ok(());
+    }
+    apply_changes(&plan.changes)?;
+    log_info("Renamed the project, reinstall the dependencies to relink its packages");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scaffold::test_scaffold;
+
+    #[test]
+    fn test_rename_name_and_scope() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = test_scaffold(tmp.path(), "acme")
+            .package_manager("bun")
+            .generate(tmp.path())
+            .unwrap();
+        let dir = project.project_dir.as_path();
+        fs::create_dir_all(dir.join("apps/web/src")).unwrap();
+        let files = [
+            (
+                "package.json",
+                "{\n  \"name\": \"acme\",\n  \"scripts\": { \"dev\": \"turbo dev --filter=@v1/web\" }\n}\n",
+            ),
+            (
+                "apps/web/package.json",
+                "{ \"name\": \"@v1/web\", \"dependencies\": { \"@v1/ui\": \"workspace:*\" } }\n",
+            ),
+            (
+                "apps/web/src/page.tsx",
+                "import { Button } from \"@v1/ui/button\";\n// see @v1/ui\nconst key = process.env.ACME_API_KEY;\n",
+            ),
+            ("apps/web/.env.example", "ACME_API_KEY=\nOTHER=\n"),
+            ("README.md", "# acme\n\nacme is built with V1.\n"),
+        ];
+        for (path, content) in files {
+            fs::write(dir.join(path), content).unwrap();
+        }
+
+        let plan = plan_rename(dir, Some("globex"), Some("@globex")).unwrap();
+        let unchanged = fs::read_to_string(dir.join("package.json")).unwrap();
+        apply_changes(&plan.changes).unwrap();
+        let read = |path: &str| fs::read_to_string(dir.join(path)).unwrap();
+        let contents: Vec<String> = files.iter().map(|(path, _)| read(path)).collect();
+        let lockfile = read_lockfile(dir).unwrap().unwrap();
+        let again = plan_rename(dir, Some("globex"), None);
+
+        assert_eq!(unchanged, files[0].1);
+        assert_eq!(plan.changes.len(), 6);
+        assert_eq!(
+            contents,
+            [
+                "{\n  \"name\": \"globex\",\n  \"scripts\": { \"dev\": \"turbo dev --filter=@globex/web\" }\n}\n",
+                "{ \"name\": \"@globex/web\", \"dependencies\": { \"@globex/ui\": \"workspace:*\" } }\n",
+                "import { Button } from \"@globex/ui/button\";\n// see @v1/ui\nconst key = process.env.GLOBEX_API_KEY;\n",
+                "GLOBEX_API_KEY=\nOTHER=\n",
+                "# globex\n\nacme is built with V1.\n",
+            ]
+        );
+        assert_eq!(plan.unrenamed, ["apps/web/src/page.tsx:2"]);
+        assert_eq!(
+            (lockfile.name.as_str(), lockfile.scope.as_str()),
+            ("globex", "globex")
+        );
+        assert!(again.is_err());
+    }
+
+    #[test]
+    fn test_rename_keeps_service_env_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = test_scaffold(tmp.path(), "next")
+            .package_manager("bun")
+            .generate(tmp.path())
+            .unwrap();
+        let dir = project.project_dir.as_path();
+        fs::create_dir_all(dir.join("apps/web/src")).unwrap();
+        let env = "NEXT_PUBLIC_SENTRY_DSN=\nNEXT_API_KEY=\n";
+        let source = "export const dsn = process.env.NEXT_PUBLIC_SENTRY_DSN;\n";
+        fs::write(dir.join("package.json"), "{ \"name\": \"next\" }\n").unwrap();
+        fs::write(dir.join("apps/web/.env.example"), env).unwrap();
+        fs::write(dir.join("apps/web/src/sentry.ts"), source).unwrap();
+
+        let plan = plan_rename(dir, Some("acme"), None).unwrap();
+        let paths: Vec<&Path> = plan
+            .changes
+            .iter()
+            .map(|c| c.path.strip_prefix(dir).unwrap())
+            .collect();
+
+        apply_changes(&plan.changes).unwrap();
+
+        assert_eq!(
+            paths,
+            [
+                Path::new("package.json"),
+                Path::new("apps/web/.env.example"),
+                Path::new(LOCKFILE_NAME)
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("apps/web/.env.example")).unwrap(),
+            "NEXT_PUBLIC_SENTRY_DSN=\nACME_API_KEY=\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("apps/web/src/sentry.ts")).unwrap(),
+            source
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use anyhow::Result;
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    cli::Service,
+    context::RunContext,
+    error::invalid_input,
+    events::{emit, Event},
+};
+
+/// How a service relates to the other services.
+pub struct ServiceRelations {
+    /// Services that are added automatically with this one.
+    pub requires: &'static [Service],
+    /// Services that can't be installed together with this one.
+    pub conflicts: &'static [Service],
+    /// Services that work well with this one but aren't needed.
+    pub suggests: &'static [Service],
+}
+
+impl Service {
+    /// The services this one requires, can't be installed with and works
+    /// well with. A conflict holds both ways, so declaring it on one of the
+    /// two services is enough.
+    pub fn relations(&self) -> ServiceRelations {
+        match self {
+            Service::Trigger => ServiceRelations {
+                requires: &[Service::Resend],
+                conflicts: &[],
+                suggests: &[Service::Upstash],
+            },
+            Service::Dub => ServiceRelations {
+                requires: &[],
+                conflicts: &[],
+                suggests: &[Service::Openpanel],
+            },
+            _ => ServiceRelations {
+                requires: &[],
+                conflicts: &[],
+                suggests: &[],
+            },
+        }
+    }
+}
+
+/// The services that will be written once the selection is resolved.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ServicePlan {
+    /// Selected services, in selection order.
+    pub selected: Vec<Service>,
+    /// Services added because another one requires them, with the service
+    /// that required them.
+    pub required: Vec<(Service, Service)>,
+    /// Selected services that are already installed and will be skipped.
+    pub skipped: Vec<Service>,
+    /// Services suggested by the plan that aren't part of it.
+    pub suggested: Vec<Service>,
+    #[serde(skip)]
+    order: Vec<Service>,
+}
+
+impl ServicePlan {
+    /// All services to write, each one after the services it requires.
+    pub fn services(&self) -> Vec<Service> {
+        self.order.clone()
+    }
+
+    fn contains(&self, service: &Service) -> bool {
+        self.selected.contains(service) || self.required.iter().any(|(s, _)| s == service)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selected.is_empty() && self.required.is_empty()
+    }
+}
+
+/// Expands the selection with required services and rejects conflicts with
+/// the selection or the installed services.
+pub fn resolve_services(selection: &[Service], installed: &[Service]) -> Result<ServicePlan> {
+    resolve_with(selection, installed, Service::relations)
+}
+
+fn resolve_with(
+    selection: &[Service],
+    installed: &[Service],
+    relations: impl Fn(&Service) -> ServiceRelations,
+) -> Result<ServicePlan> {
+    let mut plan = ServicePlan::default();
+
+    for service in selection {
+        if installed.contains(service) {
+            if !plan.skipped.contains(service) {
+                plan.skipped.push(service.clone());
+            }
+        } else if !plan.selected.contains(service) {
+            plan.selected.push(service.clone());
+        }
+    }
+
+    // keep walking the requirements so transitive ones are added too
+    let mut queue = plan.selected.clone();
+    while let Some(service) = queue.pop() {
+        for required in relations(&service).requires {
+            if !plan.contains(required) && !installed.contains(required) {
+                plan.required.push((required.clone(), service.clone()));
+                queue.push(required.clone());
+            }
+        }
+    }
+
+    let mut visited = Vec::new();
+    for service in plan.selected.clone() {
+        add_in_order(&service, &relations, &mut plan, &mut visited);
+    }
+
+    let planned = plan.services();
+    let everything: Vec<&Service> = planned.iter().chain(installed).collect();
+    for service in &planned {
+        for other in &everything {
+            let conflicting = relations(service).conflicts.contains(other)
+                || relations(other).conflicts.contains(service);
+            if conflicting && service != *other {
+                let reason = if installed.contains(other) {
+                    format!("{} is already installed", other)
+                } else {
+                    format!("{} is also selected", other)
+                };
+                return Err(invalid_input(format!(
+                    "Service {} conflicts with {} ({}), choose one of them",
+                    service, other, reason
+                )));
+            }
+        }
+    }
+
+    for service in &planned {
+        for suggested in relations(service).suggests {
+            if !planned.contains(suggested)
+                && !installed.contains(suggested)
+                && !plan.suggested.contains(suggested)
+            {
+                plan.suggested.push(suggested.clone());
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Appends `service` to the write order after the planned services it
+/// requires. `visited` stops cycles.
+fn add_in_order(
+    service: &Service,
+    relations: &impl Fn(&Service) -> ServiceRelations,
+    plan: &mut ServicePlan,
+    visited: &mut Vec<Service>,
+) {
+    if visited.contains(service) {
+        return;
+    }
+    visited.push(service.clone());
+    for required in relations(service).requires {
+        if plan.contains(required) {
+            add_in_order(required, relations, plan, visited);
+        }
+    }
+    plan.order.push(service.clone());
+}
+
+fn print_plan(plan: &ServicePlan) {
+    if plan.is_empty() {
+        if !plan.skipped.is_empty() {
+            println!(
+                "{}",
+                style("All selected services are already installed.").dim()
+            );
+        }
+        return;
+    }
+
+    println!("{}", style("Services to add:").bold());
+    for service in &plan.selected {
+        println!("  + {}", service);
+    }
+    for (service, required_by) in &plan.required {
+        println!(
+            "  + {} {}",
+            service,
+            style(format!("(required by {})", required_by)).dim()
+        );
+    }
+    for service in &plan.skipped {
+        println!("  = {} {}", service, style("(already installed)").dim());
+    }
+    if !plan.suggested.is_empty() {
+        let suggested: Vec<String> = plan.suggested.iter().map(|s| s.to_string()).collect();
+        println!(
+            "{}",
+            style(format!("Suggested: {}", suggested.join(", "))).dim()
+        );
+    }
+}
+
+/// Prints the plan and asks for confirmation when services were added that
+/// weren't selected. Returns whether to go ahead.
+pub fn confirm_plan(ctx: &RunContext, plan: &ServicePlan) -> Result<bool> {
+    if ctx.json {
+        emit(Event::ServicesResolved { plan });
+    } else {
+        print_plan(plan);
+    }
+
+    if plan.is_empty() {
+        return Ok(false);
+    }
+
+    if plan.required.is_empty() {
+        return Ok(true);
+    }
+
+    ctx.prompter.confirm("Continue with this plan?", true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    fn relations(service: &Service) -> ServiceRelations {
+        match service {
+            Service::Trigger => ServiceRelations {
+                requires: &[Service::Resend],
+                conflicts: &[],
+                suggests: &[Service::Upstash],
+            },
+            Service::Resend => ServiceRelations {
+                requires: &[Service::Dub],
+                conflicts: &[],
+                suggests: &[],
+            },
+            Service::Openpanel => ServiceRelations {
+                requires: &[],
+                conflicts: &[Service::Sentry],
+                suggests: &[],
+            },
+            _ => ServiceRelations {
+                requires: &[],
+                conflicts: &[],
+                suggests: &[],
+            },
+        }
+    }
+
+    #[test]
+    fn test_requirements_are_expanded_transitively() {
+        let plan = resolve_with(&[Service::Trigger], &[], relations).unwrap();
+        assert_eq!(plan.selected, vec![Service::Trigger]);
+        assert_eq!(
+            plan.required,
+            vec![
+                (Service::Resend, Service::Trigger),
+                (Service::Dub, Service::Resend)
+            ]
+        );
+        assert_eq!(
+            plan.services(),
+            vec![Service::Dub, Service::Resend, Service::Trigger]
+        );
+        assert_eq!(plan.suggested, vec![Service::Upstash]);
+    }
+
+    #[test]
+    fn test_services_come_after_their_requirements() {
+        let plan = resolve_with(&[Service::Trigger, Service::Resend], &[], relations).unwrap();
+        assert_eq!(plan.required, vec![(Service::Dub, Service::Resend)]);
+        assert_eq!(
+            plan.services(),
+            vec![Service::Dub, Service::Resend, Service::Trigger]
+        );
+    }
+
+    #[test]
+    fn test_installed_services_are_skipped() {
+        let plan = resolve_with(
+            &[Service::Trigger, Service::Cal],
+            &[Service::Cal, Service::Resend],
+            relations,
+        )
+        .unwrap();
+        assert_eq!(plan.services(), vec![Service::Trigger]);
+        assert_eq!(plan.skipped, vec![Service::Cal]);
+    }
+
+    #[test]
+    fn test_conflicts_are_rejected() {
+        let err = resolve_with(&[Service::Sentry, Service::Openpanel], &[], relations)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("conflicts"), "{}", err);
+
+        let err = resolve_with(&[Service::Openpanel], &[Service::Sentry], relations)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("already installed"), "{}", err);
+    }
+
+    #[test]
+    fn test_every_service_resolves_on_its_own() {
+        for service in Service::value_variants() {
+            let plan = resolve_services(std::slice::from_ref(service), &[]).unwrap();
+            for planned in plan.services() {
+                assert!(
+                    !service.relations().conflicts.contains(&planned),
+                    "{} requires {}, which it conflicts with",
+                    service,
+                    planned
+                );
+            }
+        }
+    }
+}
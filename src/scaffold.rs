@@ -0,0 +1,342 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cancel::{check_cancelled, with_cancellation, CancellationToken},
+    cleanup::{CleanupManager, CleanupTask},
+    cli::Service,
+    env,
+    error::Error,
+    git::{init_repository, is_inside_work_tree, DEFAULT_COMMIT_MESSAGE},
+    lockfile::{write_lockfile, Lockfile, DEFAULT_SCOPE, LOCKFILE_NAME},
+    logger::{log_debug, log_info, log_warn},
+    resolver::resolve_services,
+    tera::{load_templates, template_context},
+    utils::{
+        bundled_templates_path, install_workspace_dependencies, is_valid_project_name,
+        is_valid_scope,
+    },
+    workspace::{get_workspaces, process_workspace, Output, Workspace},
+};
+
+/// Generates a new V1 project without prompting.
+///
+/// ```no_run
+/// use create_v1_app::{Scaffold, Service};
+///
+/// let report = Scaffold::new("acme")
+///     .services([Service::Resend])
+///     .package_manager("bun")
+///     .generate(std::path::Path::new("."))?;
+/// println!("{} files written", report.files.len());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scaffold {
+    name: String,
+    services: Vec<Service>,
+    package_manager: String,
+    scope: String,
+    providers: Vec<String>,
+    variables: BTreeMap<String, serde_json::Value>,
+    template: Option<PathBuf>,
+    install: bool,
+    git: bool,
+    commit_message: String,
+    cancellation: Option<CancellationToken>,
+}
+
+/// What a generation produced.
+#[derive(Debug, Clone)]
+pub struct ScaffoldReport {
+    pub project_dir: PathBuf,
+    /// The services generated, including the ones added as requirements.
+    pub services: Vec<Service>,
+    /// Every file written, in order.
+    pub files: Vec<PathBuf>,
+    pub duration: Duration,
+}
+
+impl Scaffold {
+    pub fn new(name: impl Into<String>) -> Self {
+        Scaffold {
+            name: name.into(),
+            services: Vec::new(),
+            package_manager: "npm".to_string(),
+            scope: DEFAULT_SCOPE.to_string(),
+            providers: Vec::new(),
+            variables: BTreeMap::new(),
+            template: None,
+            install: false,
+            git: false,
+            commit_message: DEFAULT_COMMIT_MESSAGE.to_string(),
+            cancellation: None,
+        }
+    }
+
+    pub fn services(mut self, services: impl IntoIterator<Item = Service>) -> Self {
+        self.services = services.into_iter().collect();
+        self
+    }
+
+    pub fn package_manager(mut self, package_manager: impl Into<String>) -> Self {
+        self.package_manager = package_manager.into();
+        self
+    }
+
+    /// The npm scope of the packages, `v1` by default.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = scope.into().trim_start_matches('@').to_string();
+        self
+    }
+
+    pub fn providers(mut self, providers: impl IntoIterator<Item = String>) -> Self {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    /// Extra variables passed to the templates.
+    pub fn variables(mut self, variables: BTreeMap<String, serde_json::Value>) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    /// Loads the templates from `path` instead of the bundled ones.
+    pub fn template(mut self, path: impl Into<PathBuf>) -> Self {
+        self.template = Some(path.into());
+        self
+    }
+
+    /// Installs the dependencies once the project is generated.
+    pub fn install(mut self, install: bool) -> Self {
+        self.install = install;
+        self
+    }
+
+    /// Initializes a git repository with a `.gitignore` and commits the
+    /// project, unless it is generated inside a git work tree.
+    pub fn git(mut self, git: bool) -> Self {
+        self.git = git;
+        self
+    }
+
+    pub fn commit_message(mut self, message: impl Into<String>) -> Self {
+        self.commit_message = message.into();
+        self
+    }
+
+    /// Stops the generation and rolls it back once `token` is cancelled.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Generates the project in `target/<name>`. Everything written is
+    /// removed again when generation fails, a failed install keeps the
+    /// project so it can be retried.
+    pub fn generate(&self, target: &Path) -> Result<ScaffoldReport> {
+        let start_time = Instant::now();
+
+        is_valid_project_name(&self.name)?;
+        is_valid_scope(&self.scope)?;
+
+        let project_dir = target.join(&self.name);
+        check_project_dir(&project_dir)?;
+
+        let mut rollback = CleanupManager::new();
+        rollback.add_task(CleanupTask::RemoveDirectory(project_dir.clone()));
+
+        let token = self.cancellation.clone().unwrap_or_default();
+        let result = with_cancellation(&token, || self.write_project(&project_dir));
+        if result.is_err() {
+            rollback.cleanup();
+        }
+        let (services, mut files) = result?;
+
+        if self.install {
+            with_cancellation(&token, check_cancelled)?;
+            install_workspace_dependencies(
+                &project_dir,
+                &self.package_manager,
+                Some(ProgressBar::hidden()),
+            )?;
+        }
+
+        if self.git {
+            with_cancellation(&token, check_cancelled)?;
+            files.extend(self.init_git(target, &project_dir));
+        }
+
+        Ok(ScaffoldReport {
+            project_dir,
+            services,
+            files,
+            duration: start_time.elapsed(),
+        })
+    }
+
+    /// A project that can't be committed is still usable, so git failures
+    /// are only warned about.
+    fn init_git(&self, target: &Path, project_dir: &Path) -> Option<PathBuf> {
+        if is_inside_work_tree(target) {
+            log_info("Already inside a git repository, skipping git init");
+            return None;
+        }
+
+        match init_repository(project_dir, &self.package_manager, &self.commit_message) {
+            Ok(gitignore) => {
+                log_info("Initialized a git repository");
+                gitignore
+            }
+            Err(e) => {
+                log_warn(&format!("Could not initialize a git repository: {}", e));
+                None
+            }
+        }
+    }
+
+    fn write_project(&self, project_dir: &Path) -> Result<(Vec<Service>, Vec<PathBuf>)> {
+        let services = resolve_services(&self.services, &[])?.services();
+
+        let templates_root = self.template.clone().unwrap_or_else(bundled_templates_path);
+        let tera = load_templates(&templates_root)?;
+
+        log_debug(&format!(
+            "Creating new app: {} with {} service(s) by {}",
+            self.name,
+            services.len(),
+            self.package_manager
+        ));
+
+        let lockfile = Lockfile {
+            services: services.clone(),
+            providers: self.providers.clone(),
+            variables: self.variables.clone(),
+            ..Lockfile::new(&self.name, &self.scope, &self.package_manager)
+        };
+        let context = template_context(&lockfile);
+
+        fs::create_dir_all(project_dir.join("apps"))?;
+        fs::create_dir_all(project_dir.join("packages"))?;
+
+        let mut workspaces = get_workspaces(&templates_root, project_dir, &[]);
+        for service in &services {
+            let source_path = templates_root.join("services").join(service.to_string());
+            if !source_path.exists() {
+                return Err(Error::TemplateNotFound(source_path.display().to_string()).into());
+            }
+            workspaces.push(Workspace {
+                name: service.to_string(),
+                source_path,
+                dest_path: project_dir.join("packages").join(service.to_string()),
+                is_root: false,
+            });
+        }
+
+        let mut files = Vec::new();
+        let total_steps = workspaces.len() + 1;
+        for (step, workspace) in workspaces.iter().enumerate() {
+            check_cancelled()?;
+            log_info(&format!(
+                "[{}/{}] Processing workspace: {}",
+                step + 1,
+                total_steps,
+                workspace.name
+            ));
+            files.extend(process_workspace(
+                workspace,
+                &tera,
+                &templates_root,
+                &context,
+                &self.package_manager,
+                &mut Output::Disk,
+            )?);
+        }
+
+        check_cancelled()?;
+        for service in &services {
+            files.extend(env::add_service_env(project_dir, service)?);
+        }
+
+        write_lockfile(project_dir, &lockfile)?;
+        files.push(project_dir.join(LOCKFILE_NAME));
+
+        Ok((services, files))
+    }
+}
+
+/// Fails when the project directory exists and isn't empty, so a failed
+/// generation never removes files it didn't create.
+pub fn check_project_dir(project_dir: &Path) -> Result<()> {
+    let is_empty = fs::read_dir(project_dir)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(true);
+    if !is_empty {
+        return Err(Error::ProjectExists(project_dir.to_path_buf()).into());
+    }
+    Ok(())
+}
+
+/// Templates with the workspaces every project has, a root `package.json`
+/// for each package manager and a README, for the tests generating projects.
+#[cfg(test)]
+pub fn test_templates(root: &Path) -> PathBuf {
+    let templates = root.join("templates");
+    for dir in [
+        "apps/web/src",
+        "apps/api",
+        "apps/app",
+        "packages/ui",
+        "packages/logger",
+    ] {
+        fs::create_dir_all(templates.join(dir)).unwrap();
+    }
+    for package_manager in ["npm", "pnpm", "yarn", "bun"] {
+        fs::write(
+            templates.join(format!("package.json.{}.tera", package_manager)),
+            r#"{ "name": "{{ project_name }}", "packageManager": "{{ package_manager }}@1.0.0" }"#,
+        )
+        .unwrap();
+    }
+    fs::write(templates.join("README.md"), "# V1\n").unwrap();
+    templates
+}
+
+/// Generates `name` from `test_templates` once the caller set the rest up.
+#[cfg(test)]
+pub fn test_scaffold(root: &Path, name: &str) -> Scaffold {
+    Scaffold::new(name).template(test_templates(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_into_own_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+
+        let scaffold = test_scaffold(root, "acme")
+            .package_manager("bun")
+            .scope("@acme");
+        let report = scaffold.generate(root).unwrap();
+        let second = scaffold.generate(root);
+
+        let package_json = fs::read_to_string(root.join("acme/package.json")).unwrap();
+        let lockfile = crate::lockfile::read_lockfile(&report.project_dir).unwrap();
+
+        assert_eq!(report.project_dir, root.join("acme"));
+        assert_eq!(report.files.len(), 3);
+        assert!(package_json.contains(r#""name": "acme""#));
+        assert!(package_json.contains(r#""packageManager": "bun@1.0.0""#));
+        assert_eq!(lockfile.unwrap().scope, "acme");
+        assert!(second.is_err());
+    }
+}
@@ -1,112 +1,181 @@
 use anyhow::Result;
 use clap::ValueEnum;
-use dialoguer::{theme::ColorfulTheme, Confirm, MultiSelect};
+use std::path::{Path, PathBuf};
 use tera::Context;
 
 use crate::{
+    cancel::check_cancelled,
     cleanup,
     cli::Service,
-    logger::log_info,
-    tera::TERA,
-    utils::{get_package_json, get_templates_path, PackageJson},
-    workspace::{process_workspace, Workspace},
-    CLEANUP_MANAGER,
+    env,
+    error::{invalid_input, Error},
+    events::{emit, Event},
+    lockfile::{project_scope, update_lockfile},
+    logger::{log_info, log_trace},
+    package_json::PackageJson,
+    prompt::Prompter,
+    tera::load_templates,
+    workspace::{process_workspace, Output, Workspace},
 };
 
-pub fn select_services() -> Result<Vec<Service>> {
-    let add_services = Confirm::with_theme(&ColorfulTheme::default())
-        .with_prompt("Do you want to add any services?")
-        .default(true)
-        .interact()
-        .map_err(|e| anyhow::anyhow!("Failed to prompt for service addition: {}", e))?;
+/// Prompts for services to add, leaving out the `installed` ones.
+pub fn select_services(
+    prompter: &Prompter,
+    defaults: &[Service],
+    installed: &[Service],
+) -> Result<Vec<Service>> {
+    let available: Vec<&Service> = Service::value_variants()
+        .iter()
+        .filter(|v| !installed.contains(v))
+        .collect();
+
+    if available.is_empty() {
+        log_info("Every service is already installed");
+        return Ok(Vec::new());
+    }
+
+    let add_services = prompter.confirm("Do you want to add any services?", true)?;
 
     if !add_services {
         return Ok(Vec::new());
     }
 
-    let services: Vec<String> = Service::value_variants()
-        .into_iter()
-        .map(|v| {
-            let name = v.to_string();
-            let help = v
-                .to_possible_value()
-                .unwrap()
-                .get_help()
-                .unwrap()
-                .to_string();
-            format!("{name}: {help}")
-        })
+    let services: Vec<String> = available
+        .iter()
+        .map(|v| format!("{}: {}", v, v.description()))
         .collect();
 
-    let selections = MultiSelect::with_theme(&ColorfulTheme::default())
-        .with_prompt("Select services to add")
-        .items(&services)
-        .interact()
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    let checked: Vec<bool> = available.iter().map(|v| defaults.contains(v)).collect();
+    let selections = prompter.multi_select("Select services to add", &services, &checked)?;
 
     let result = selections
         .into_iter()
-        .map(|i| services[i].to_string())
-        .map(|s| {
-            let name = s.split(':').next().unwrap();
-            Service::from_str(name, false).expect("Invalid service")
-        })
+        .map(|i| available[i].clone())
         .collect::<Vec<_>>();
 
     Ok(result)
 }
 
-pub fn add_services(workspaces: &mut Vec<Workspace>, services: &[Service]) -> Result<()> {
-    let tera = TERA.lock().unwrap();
+pub fn service_workspace(
+    project_dir: &Path,
+    templates_root: &Path,
+    service: &Service,
+) -> Workspace {
+    Workspace {
+        name: service.to_string(),
+        source_path: templates_root.join("services").join(service.to_string()),
+        dest_path: project_dir.join("packages").join(service.to_string()),
+        is_root: false,
+    }
+}
 
-    let PackageJson {
-        name,
-        package_manager,
-    } = get_package_json(None)?;
+/// Writes the packages and environment variables of already resolved
+/// services and records them in the lockfile. Returns the files written.
+pub fn generate_services(
+    project_dir: &Path,
+    templates_root: &Path,
+    services: &[Service],
+) -> Result<Vec<PathBuf>> {
+    let tera = load_templates(templates_root)?;
+
+    let package_json = PackageJson::read(project_dir)?;
+    let name = package_json.name()?;
+    let package_manager = package_json.package_manager()?;
 
     let mut context = Context::new();
-    context.insert("project_name", &name);
-    context.insert("package_manager", &package_manager);
+    context.insert("project_name", name);
+    context.insert("package_manager", package_manager);
+    context.insert("scope", &project_scope(project_dir)?);
+    log_trace(&format!(
+        "Template context: {}",
+        context.clone().into_json()
+    ));
+
+    let workspaces: Vec<Workspace> = services
+        .iter()
+        .map(|service| service_workspace(project_dir, templates_root, service))
+        .collect();
+    for workspace in &workspaces {
+        if !workspace.source_path.exists() {
+            return Err(
+                Error::TemplateNotFound(workspace.source_path.display().to_string()).into(),
+            );
+        }
+    }
 
-    let templates_root = get_templates_path();
+    // we only add new files
+    let mut files = Vec::new();
+    for workspace in &workspaces {
+        check_cancelled()?;
+        log_info(&format!("Adding service: {}", workspace.name));
+        files.extend(process_workspace(
+            workspace,
+            &tera,
+            templates_root,
+            &context,
+            package_manager,
+            &mut Output::Disk,
+        )?);
+        emit(Event::ServiceAdded {
+            name: &workspace.name,
+            path: &workspace.dest_path,
+        });
+        // TODO: install dependencies for newly added service packages
+    }
+
+    check_cancelled()?;
+    for service in services {
+        files.extend(env::add_service_env(project_dir, service)?);
+    }
 
-    let current_dir = std::env::current_dir()?;
+    update_lockfile(project_dir, |lockfile| {
+        for service in services {
+            if !lockfile.services.contains(service) {
+                lockfile.services.push(service.clone());
+            }
+        }
+    })?;
 
-    let mut new_workspaces: Vec<Workspace> = Vec::new();
+    Ok(files)
+}
 
+/// Returns the services whose packages exist in the project.
+pub fn installed_services(project_dir: &Path) -> Vec<Service> {
+    Service::value_variants()
+        .iter()
+        .filter(|service| {
+            project_dir
+                .join("packages")
+                .join(service.to_string())
+                .exists()
+        })
+        .cloned()
+        .collect()
+}
+
+pub fn remove_services(project_dir: &Path, services: &[Service]) -> Result<()> {
+    let installed = installed_services(project_dir);
     for service in services {
-        let service_template_path = templates_root.join("services").join(service.to_string());
-        if !service_template_path.exists() {
-            return Err(anyhow::anyhow!(
-                "Service template not found for: {}",
-                service.to_string()
-            ));
+        if !installed.contains(service) {
+            return Err(invalid_input(format!(
+                "Service {} is not installed",
+                service
+            )));
         }
-
-        let workspace = Workspace {
-            name: service.to_string(),
-            source_path: service_template_path.clone(),
-            dest_path: current_dir.join("packages").join(service.to_string()),
-            is_root: false,
-        };
-
-        new_workspaces.push(workspace.clone());
-        workspaces.push(workspace.clone());
-
-        {
-            let mut manager = CLEANUP_MANAGER.lock().unwrap();
-            manager.add_task(cleanup::CleanupTask::RemoveService {
-                project_dir: current_dir.clone(),
-                service_name: service.to_string(),
-            });
+        for dependent in installed.iter().filter(|s| !services.contains(s)) {
+            if dependent.relations().requires.contains(service) {
+                return Err(invalid_input(format!(
+                    "Service {} is required by {}, remove both together",
+                    service, dependent
+                )));
+            }
         }
     }
 
-    // we only add new files
-    for workspace in new_workspaces {
-        log_info(&format!("Adding service: {}", workspace.name));
-        process_workspace(&workspace, &tera, &context, &package_manager)?;
-        // TODO: install dependencies for newly added service packages
+    for service in services {
+        check_cancelled()?;
+        log_info(&format!("Removing service: {}", service));
+        cleanup::remove_service(project_dir, &service.to_string())?;
     }
 
     Ok(())
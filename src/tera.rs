@@ -1,30 +1,65 @@
 use anyhow::Result;
-use once_cell::sync::Lazy;
-use std::sync::Arc;
-use std::sync::Mutex;
-use tera::Tera;
+use std::path::Path;
+use tera::{Context, Tera};
 use walkdir::WalkDir;
 
-pub static TERA: Lazy<Arc<Mutex<Tera>>> = Lazy::new(|| Arc::new(Mutex::new(Tera::default())));
+use crate::{
+    error::Error,
+    lockfile::{scoped_package_name, Lockfile},
+    logger::log_trace,
+};
 
-pub fn initialize_tera() -> Result<()> {
-    let mut tera = TERA.lock().expect("Failed to lock Tera instance");
+/// Loads every `.tera` file under `templates_root` into a new Tera instance,
+/// named by its path relative to the root.
+pub fn load_templates(templates_root: &Path) -> Result<Tera> {
+    let mut tera = Tera::default();
 
-    for entry in WalkDir::new("templates").into_iter().filter_map(|e| e.ok()) {
-        if entry.file_type().is_file()
-            && entry.path().extension().map_or(false, |ext| ext == "tera")
+    for entry in WalkDir::new(templates_root)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "tera")
         {
             let template_path = entry
                 .path()
-                .strip_prefix("templates")
+                .strip_prefix(templates_root)
                 .expect("Failed to strip prefix");
             let template_name = template_path
                 .to_str()
                 .expect("Failed to convert template path to string");
             tera.add_template_file(entry.path(), Some(template_name))
-                .map_err(|e| anyhow::anyhow!("Failed to add template file: {}", e))?;
+                .map_err(|e| Error::TemplateRender {
+                    template: template_name.to_string(),
+                    message: e.to_string(),
+                })?;
         }
     }
 
-    Ok(())
+    Ok(tera)
+}
+
+/// The values the templates of a project are rendered with.
+pub fn template_context(lockfile: &Lockfile) -> Context {
+    let mut context = Context::new();
+    for (key, value) in &lockfile.variables {
+        context.insert(key, value);
+    }
+    context.insert("providers", &lockfile.providers);
+    context.insert("scope", &lockfile.scope);
+    context.insert("project_name", &lockfile.name);
+    context.insert("package_manager", &lockfile.package_manager);
+    log_trace(&format!(
+        "Template context: {}",
+        context.clone().into_json()
+    ));
+    context
+}
+
+/// The values the templates of a workspace added from a blueprint are
+/// rendered with, the project's and its own names.
+pub fn workspace_context(lockfile: &Lockfile, name: &str) -> Context {
+    let mut context = template_context(lockfile);
+    context.insert("workspace_name", name);
+    context.insert("package_name", &scoped_package_name(&lockfile.scope, name));
+    context
 }
@@ -1,68 +1,62 @@
 use std::{
+    io::{BufRead, BufReader, Read},
     path::{Path, PathBuf},
-    sync::Arc,
+    process::{Command, ExitStatus, Stdio},
+    sync::{mpsc, Arc},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Result};
+use console::style;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use regex::Regex;
+use walkdir::WalkDir;
 
 use crate::{
-    logger::{log_debug, log_error},
+    error::{invalid_input, Error},
+    events::{emit, Event},
+    git::BUILD_DIRS,
+    lockfile::{read_lockfile, LOCKFILE_NAME},
+    logger::{log_debug, log_error, log_trace},
+    package_json::{PackageJson, PACKAGE_MANAGERS},
+    prompt::Prompter,
     workspace::get_workspaces,
 };
 
-pub struct PackageJson {
-    pub name: String,
-    pub package_manager: String,
-}
-
-pub fn get_package_json(dir: Option<&Path>) -> Result<PackageJson> {
-    let current_dir = dir.unwrap_or_else(|| Path::new("."));
-    let package_json_path = current_dir.join("package.json");
+/// Walks up from `start` to the root of the V1 project containing it, the
+/// nearest directory with a lockfile or a package.json declaring workspaces.
+pub fn find_project_root(start: &Path) -> Result<PathBuf> {
+    for dir in start.ancestors() {
+        if dir.join(LOCKFILE_NAME).is_file() {
+            return Ok(dir.to_path_buf());
+        }
 
-    if !package_json_path.exists() {
-        return Err(anyhow!("package.json not found in the current directory"));
+        if dir.join("package.json").is_file() && PackageJson::read(dir)?.workspaces.is_some() {
+            return Ok(dir.to_path_buf());
+        }
     }
 
-    let package_json = std::fs::read_to_string(package_json_path)?;
-    let package_json: serde_json::Value = serde_json::from_str(&package_json)?;
-
-    let name = package_json["name"]
-        .as_str()
-        .ok_or_else(|| anyhow!("name not found in package.json"))?;
-
-    let package_manager = package_json["packageManager"]
-        .as_str()
-        .unwrap()
-        .split('@')
-        .next()
-        .ok_or_else(|| anyhow!("packageManager not found in package.json"))?;
-
-    Ok(PackageJson {
-        name: name.to_string(),
-        package_manager: package_manager.to_string(),
-    })
+    Err(Error::NotAProject(start.to_path_buf()).into())
 }
 
-pub fn select_package_manager() -> Result<String> {
-    let package_managers = vec!["npm", "yarn", "pnpm", "bun"];
-    let selection = dialoguer::Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
-        .with_prompt("Select a package manager")
-        .items(&package_managers)
-        .default(0)
-        .interact()
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+pub fn select_package_manager(prompter: &Prompter, default: Option<&str>) -> Result<String> {
+    let default = PACKAGE_MANAGERS
+        .iter()
+        .position(|pm| Some(*pm) == default)
+        .unwrap_or(0);
+    let selection = prompter.select("Select a package manager", &PACKAGE_MANAGERS, default)?;
 
-    Ok(package_managers[selection].to_string())
+    Ok(PACKAGE_MANAGERS[selection].to_string())
 }
 
-pub fn confirm_package_manager(package_manager: Option<&str>) -> Result<String> {
+pub fn confirm_package_manager(
+    prompter: &Prompter,
+    package_manager: Option<&str>,
+) -> Result<String> {
     match package_manager {
         Some(pm) => Ok(pm.to_string()),
-        None => select_package_manager(),
+        None => select_package_manager(prompter, None),
     }
 }
 
@@ -74,7 +68,10 @@ pub fn install_all_dependencies(project_path: &Path, package_manager: &str) -> R
         .unwrap()
         .progress_chars("##-");
 
-    let workspaces = get_workspaces(Path::new("templates"), project_path);
+    let added = read_lockfile(project_path)?
+        .map(|lockfile| lockfile.workspaces)
+        .unwrap_or_default();
+    let workspaces = get_workspaces(Path::new("templates"), project_path, &added);
     let mut handles = vec![];
 
     for workspace in workspaces {
@@ -105,7 +102,11 @@ pub fn install_all_dependencies(project_path: &Path, package_manager: &str) -> R
     }
 
     if results.iter().any(|r| r.is_err()) {
-        return Err(anyhow!("One or more installations failed"));
+        return Err(Error::InstallFailed {
+            path: project_path.to_path_buf(),
+            message: "one or more installations failed".to_string(),
+        }
+        .into());
     }
 
     multi_progress.clear()?;
@@ -113,6 +114,72 @@ pub fn install_all_dependencies(project_path: &Path, package_manager: &str) -> R
     Ok(())
 }
 
+struct InstallOutput {
+    status: ExitStatus,
+    stdout: String,
+    stderr: String,
+}
+
+fn forward_lines(
+    pipe: impl Read + Send + 'static,
+    stream: &'static str,
+    sender: mpsc::Sender<(&'static str, String)>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines().map_while(|line| line.ok()) {
+            let _ = sender.send((stream, line));
+        }
+    })
+}
+
+/// Runs `<package manager> install`, emitting its output line by line as
+/// it comes.
+fn run_install(workspace_path: &Path, package_manager: &str) -> Result<InstallOutput> {
+    let mut child = Command::new(package_manager)
+        .arg("install")
+        .current_dir(workspace_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(forward_lines(stdout, "stdout", sender.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(forward_lines(stderr, "stderr", sender.clone()));
+    }
+    drop(sender);
+
+    // events are emitted from this thread, where the listener is
+    let mut output = InstallOutput {
+        status: ExitStatus::default(),
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+    for (stream, line) in receiver {
+        emit(Event::InstallOutput {
+            path: workspace_path,
+            stream,
+            line: &line,
+        });
+        let buffer = if stream == "stdout" {
+            &mut output.stdout
+        } else {
+            &mut output.stderr
+        };
+        buffer.push_str(&line);
+        buffer.push('\n');
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+
+    output.status = child.wait()?;
+    Ok(output)
+}
+
 pub fn install_workspace_dependencies(
     workspace_path: &Path,
     package_manager: &str,
@@ -124,6 +191,11 @@ pub fn install_workspace_dependencies(
     ));
 
     log_debug(&format!("Running command: {} install", package_manager));
+    emit(Event::InstallStarted {
+        path: workspace_path,
+        package_manager,
+    });
+    let start_time = Instant::now();
 
     let pb = progress_bar.unwrap_or_else(|| {
         let pb = ProgressBar::new_spinner();
@@ -141,36 +213,44 @@ pub fn install_workspace_dependencies(
         pb
     });
 
-    let output = std::process::Command::new(package_manager)
-        .arg("install")
-        .current_dir(workspace_path)
-        .output()?;
+    let output = run_install(workspace_path, package_manager)?;
 
     log_debug(&format!(
         "Installation command completed for {}",
         workspace_path.display()
     ));
+    log_trace(&format!(
+        "{} install output in {}:\nStdout: {}\nStderr: {}",
+        package_manager,
+        workspace_path.display(),
+        output.stdout,
+        output.stderr
+    ));
+    emit(Event::InstallFinished {
+        path: workspace_path,
+        success: output.status.success(),
+        duration_ms: start_time.elapsed().as_millis(),
+    });
 
     if !output.status.success() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let error_message = format!(
-            "Installation failed in {}:\nStdout: {}\nStderr: {}",
-            workspace_path.display(),
-            stdout,
-            stderr
-        );
-        log_error(&error_message);
+        let error = Error::InstallFailed {
+            path: workspace_path.to_path_buf(),
+            message: format!("\nStdout: {}\nStderr: {}", output.stdout, output.stderr),
+        };
+        log_error(&error.to_string());
         pb.finish_with_message("Installation failed");
-        return Err(anyhow!(error_message));
+        return Err(error.into());
     }
 
     let node_modules_path = workspace_path.join("node_modules");
     if !node_modules_path.exists() {
-        let error_message = format!("node_modules not created in {}", workspace_path.display());
-        log_error(&error_message);
+        let error = Error::InstallFailed {
+            path: workspace_path.to_path_buf(),
+            message: "node_modules was not created".to_string(),
+        };
+        log_error(&error.to_string());
         pb.finish_with_message("Installation failed");
-        return Err(anyhow!(error_message));
+        return Err(error.into());
     }
 
     pb.finish_with_message(format!("Installed {}", workspace_path.display()));
@@ -180,28 +260,99 @@ pub fn install_workspace_dependencies(
 
 pub fn is_valid_project_name(name: &str) -> Result<()> {
     if name.is_empty() {
-        return Err(anyhow!("Project name cannot be empty"));
+        return Err(invalid_input("Project name cannot be empty"));
     }
 
     if !name.chars().next().unwrap().is_ascii_alphabetic() {
-        return Err(anyhow!("Project name must start with a letter"));
+        return Err(invalid_input("Project name must start with a letter"));
     }
 
     let re = Regex::new(r"^[a-zA-Z][a-zA-Z0-9_-]*$").unwrap();
     if !re.is_match(name) {
-        return Err(anyhow!(
-            "Project name can only contain letters, numbers, dashes, and underscores"
+        return Err(invalid_input(
+            "Project name can only contain letters, numbers, dashes, and underscores",
         ));
     }
 
     if name.len() > 20 {
-        return Err(anyhow!("Project name is too long (maximum 20 characters)"));
+        return Err(invalid_input(
+            "Project name is too long (maximum 20 characters)",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Prints rows as a plain table with a bold header, padding each column to
+/// its widest cell.
+pub fn print_table<const N: usize>(headers: &[&str; N], rows: &[[String; N]]) {
+    let mut widths = headers.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let header = headers
+        .iter()
+        .zip(widths)
+        .map(|(h, w)| format!("{:<w$}", h, w = w))
+        .collect::<Vec<_>>()
+        .join("  ");
+    println!("{}", style(header.trim_end()).bold());
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(c, w)| format!("{:<w$}", c, w = w))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Validates an npm scope, given without the leading `@`.
+pub fn is_valid_scope(scope: &str) -> Result<()> {
+    let re = Regex::new(r"^[a-z0-9][a-z0-9._~-]*$").unwrap();
+    if !re.is_match(scope) {
+        return Err(invalid_input(format!(
+            "Invalid npm scope: {}, it can only contain lowercase letters, numbers, dashes, dots and underscores",
+            scope
+        )));
+    }
+
+    if scope.len() > 214 {
+        return Err(invalid_input(
+            "npm scope is too long (maximum 214 characters)",
+        ));
     }
 
     Ok(())
 }
 
-pub fn get_templates_path() -> PathBuf {
+/// Every file and directory of the project, leaving out `node_modules`,
+/// `.git` and the build output git ignores.
+pub fn project_files(project_dir: &Path) -> impl Iterator<Item = walkdir::DirEntry> {
+    WalkDir::new(project_dir)
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() == 0
+                || !e.file_name().to_str().is_some_and(|name| {
+                    name == "node_modules" || name == ".git" || BUILD_DIRS.contains(&name)
+                })
+        })
+        .filter_map(|e| e.ok())
+}
+
+/// Whether the file is TS or JS source that imports can be read from.
+pub fn is_source_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["ts", "tsx", "js", "jsx", "mts", "cts", "mjs", "cjs"].contains(&ext))
+}
+
+/// The templates shipped with the CLI, whatever a config selected.
+pub fn bundled_templates_path() -> PathBuf {
     let manifest_dir = env!("CARGO_MANIFEST_DIR");
     PathBuf::from(manifest_dir).join("templates") // templates/ inside the CLI project root
 }
@@ -229,4 +380,56 @@ mod tests {
         assert!(is_valid_project_name("MY_PROJECT!").is_err());
         assert!(is_valid_project_name("myproject12345678901234567890").is_err());
     }
+
+    #[test]
+    fn test_scopes() {
+        assert!(is_valid_scope("v1").is_ok());
+        assert!(is_valid_scope("acme").is_ok());
+        assert!(is_valid_scope("my-org.io").is_ok());
+        assert!(is_valid_scope("").is_err());
+        assert!(is_valid_scope("Acme").is_err());
+        assert!(is_valid_scope("@acme").is_err());
+        assert!(is_valid_scope(".acme").is_err());
+        assert!(is_valid_scope("acme/ui").is_err());
+    }
+
+    #[test]
+    fn test_project_root_is_found_from_a_workspace() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let web = root.join("apps").join("web");
+        std::fs::create_dir_all(web.join("src")).unwrap();
+        std::fs::write(
+            root.join("package.json"),
+            r#"{ "name": "acme", "workspaces": ["apps/*", "packages/*"] }"#,
+        )
+        .unwrap();
+        std::fs::write(web.join("package.json"), r#"{ "name": "@v1/web" }"#).unwrap();
+
+        let found = find_project_root(&web.join("src")).unwrap();
+
+        assert_eq!(found, root);
+    }
+
+    #[test]
+    fn test_project_files_leave_out_build_output() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for dir in [
+            "apps/web/src",
+            "apps/web/.next",
+            "node_modules/react",
+            "packages/ui/dist",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join("index.js"), "").unwrap();
+        }
+
+        let files: Vec<PathBuf> = project_files(root)
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path())
+            .collect();
+
+        assert_eq!(files, [root.join("apps/web/src/index.js")]);
+    }
 }
@@ -1,12 +1,19 @@
 use anyhow::Result;
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
 };
 use tera::Tera;
 use walkdir::WalkDir;
 
+use crate::blueprint::blueprints_dir;
+use crate::cancel::check_cancelled;
+use crate::error::Error;
+use crate::events::{emit, Event};
+use crate::lockfile::AddedWorkspace;
 use crate::logger::log_debug;
+
 #[derive(Clone)]
 pub struct Workspace {
     pub name: String,
@@ -15,13 +22,70 @@ pub struct Workspace {
     pub is_root: bool,
 }
 
+/// Where `process_workspace` puts the files it renders.
+pub enum Output {
+    Disk,
+    /// Kept by destination path instead, nothing is written or emitted.
+    Memory(BTreeMap<PathBuf, Vec<u8>>),
+}
+
+impl Output {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        match self {
+            Output::Disk => {
+                fs::create_dir_all(path.parent().unwrap())?;
+                fs::write(path, contents)
+                    .map_err(|e| anyhow::anyhow!("Failed to write file {}: {}", path.display(), e))
+            }
+            Output::Memory(files) => {
+                files.insert(path.to_path_buf(), contents.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    fn copy(&mut self, from: &Path, to: &Path) -> Result<()> {
+        match self {
+            Output::Disk => {
+                fs::create_dir_all(to.parent().unwrap())?;
+                fs::copy(from, to).map_err(|e| {
+                    anyhow::anyhow!(
+                        "Failed to copy file from {} to {}: {}",
+                        from.display(),
+                        to.display(),
+                        e
+                    )
+                })?;
+            }
+            Output::Memory(files) => {
+                files.insert(to.to_path_buf(), fs::read(from)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&self, event: Event) {
+        if let Output::Disk = self {
+            emit(event);
+        }
+    }
+}
+
+/// Renders and copies the workspace's files, returning the files written.
+/// Templates are looked up by their path relative to `templates_root`.
 pub fn process_workspace(
     workspace: &Workspace,
     tera: &Tera,
+    templates_root: &Path,
     context: &tera::Context,
     package_manager: &str,
-) -> Result<()> {
+    output: &mut Output,
+) -> Result<Vec<PathBuf>> {
     log_debug(&format!("Processing workspace: {}", workspace.name));
+    output.emit(Event::WorkspaceStarted {
+        name: &workspace.name,
+        path: &workspace.dest_path,
+    });
 
     let walker = if workspace.is_root {
         WalkDir::new(&workspace.source_path)
@@ -31,12 +95,19 @@ pub fn process_workspace(
         WalkDir::new(&workspace.source_path)
     };
 
+    if !workspace.source_path.is_dir() {
+        return Err(Error::TemplateNotFound(workspace.source_path.display().to_string()).into());
+    }
+
+    let mut written = Vec::new();
     for entry in walker {
         let entry = entry.map_err(|e| anyhow::anyhow!("Failed to read directory entry: {}", e))?;
         let path = entry.path();
 
         if path.is_file() {
-            let is_template = path.extension().map_or(false, |ext| ext == "tera");
+            check_cancelled()?;
+
+            let is_template = path.extension().is_some_and(|ext| ext == "tera");
             let relative_path = path
                 .strip_prefix(&workspace.source_path)
                 .map_err(|e| anyhow::anyhow!("Failed to strip prefix from path: {}", e))?;
@@ -45,14 +116,34 @@ pub fn process_workspace(
                 .to_str()
                 .ok_or_else(|| anyhow::anyhow!("Failed to convert path to string"))?;
 
-            if is_template {
-                process_template(workspace, tera, context, package_manager, path, file_name)?;
+            let dest_path = if is_template {
+                let template_name = path
+                    .strip_prefix(templates_root)
+                    .ok()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| Error::TemplateNotFound(path.display().to_string()))?;
+                process_template(
+                    workspace,
+                    tera,
+                    context,
+                    package_manager,
+                    path,
+                    template_name,
+                    output,
+                )?
             } else {
-                copy_non_template_file(workspace, path, file_name, package_manager)?;
-            }
+                copy_non_template_file(workspace, path, file_name, package_manager, output)?
+            };
+            written.extend(dest_path);
         }
     }
-    Ok(())
+
+    output.emit(Event::WorkspaceFinished {
+        name: &workspace.name,
+        path: &workspace.dest_path,
+        files_written: written.len(),
+    });
+    Ok(written)
 }
 
 fn process_template(
@@ -62,76 +153,95 @@ fn process_template(
     package_manager: &str,
     path: &Path,
     template_name: &str,
-) -> Result<()> {
+    output: &mut Output,
+) -> Result<Option<PathBuf>> {
     log_debug(&format!("Processing template: {}", template_name));
 
+    let relative_dest_path = path
+        .strip_prefix(&workspace.source_path)
+        .map_err(|e| anyhow::anyhow!("Failed to strip prefix from destination path: {}", e))?;
+    let file_name = relative_dest_path.to_str().unwrap_or_default();
+
     // Skip pnpm-workspace.yaml if package manager is not pnpm
-    if template_name.ends_with("pnpm-workspace.yaml.tera") && package_manager != "pnpm" {
-        log_debug(&format!(
-            "Skipping pnpm-workspace.yaml for non-pnpm project"
-        ));
-        return Ok(());
+    if file_name.ends_with("pnpm-workspace.yaml.tera") && package_manager != "pnpm" {
+        log_debug("Skipping pnpm-workspace.yaml for non-pnpm project");
+        output.emit(Event::FileSkipped {
+            source: path,
+            reason: "pnpm-workspace.yaml is only used by pnpm",
+        });
+        return Ok(None);
     }
 
-    // Handle package.json.*.tera files
-    if template_name.starts_with("package.json.") {
+    // Handle package.json.*.tera files, a workspace's own package.json.tera
+    // is rendered like any other template
+    if file_name.starts_with("package.json.")
+        && (workspace.is_root || file_name != "package.json.tera")
+    {
         if !workspace.is_root {
-            log_debug(&format!(
-                "Skipping package.json template for non-root workspace"
-            ));
-            return Ok(()); // Skip for non-root workspaces
+            log_debug("Skipping package.json template for non-root workspace");
+            return Ok(None); // Skip for non-root workspaces
         }
-        let pm_suffix = template_name
+        let pm_suffix = file_name
             .trim_end_matches(".tera")
             .split('.')
-            .last()
+            .next_back()
             .unwrap_or("");
         if pm_suffix != package_manager && pm_suffix != "base" {
             log_debug(&format!(
                 "Skipping non-matching package.json template: {}",
                 template_name
             ));
-            return Ok(());
+            output.emit(Event::FileSkipped {
+                source: path,
+                reason: "package.json template for another package manager",
+            });
+            return Ok(None);
         }
     }
 
     let rendered = tera
         .render(template_name, context)
-        .map_err(|e| anyhow::anyhow!("Failed to render template {}: {}", template_name, e))?;
+        .map_err(|e| Error::TemplateRender {
+            template: template_name.to_string(),
+            message: render_error_message(&e),
+        })?;
 
     // Skip empty templates (conditionally excluded)
     if rendered.trim().is_empty() {
         log_debug(&format!("Skipping empty template: {}", template_name));
-        return Ok(());
+        output.emit(Event::FileSkipped {
+            source: path,
+            reason: "template rendered empty",
+        });
+        return Ok(None);
     }
 
-    let relative_dest_path = path
-        .strip_prefix(&workspace.source_path)
-        .map_err(|e| anyhow::anyhow!("Failed to strip prefix from destination path: {}", e))?;
     let mut dest_path = workspace
         .dest_path
         .join(relative_dest_path)
         .with_extension("");
 
     // Rename package.json.{pm}.tera to package.json for root workspace
-    if workspace.is_root && template_name.starts_with("package.json.") {
-        if template_name.ends_with("base.tera") {
-            log_debug(&format!("Skipping base package.json template"));
-            return Ok(());
+    if workspace.is_root && file_name.starts_with("package.json.") {
+        if file_name.ends_with("base.tera") {
+            log_debug("Skipping base package.json template");
+            return Ok(None);
         }
         dest_path = workspace.dest_path.join("package.json");
     }
 
-    fs::create_dir_all(dest_path.parent().unwrap())?;
-    fs::write(&dest_path, rendered)
-        .map_err(|e| anyhow::anyhow!("Failed to write file {}: {}", dest_path.display(), e))?;
+    output.write(&dest_path, rendered.as_bytes())?;
 
     log_debug(&format!(
         "Rendered template: {} -> {}",
         template_name,
         dest_path.display()
     ));
-    Ok(())
+    output.emit(Event::FileWritten {
+        path: &dest_path,
+        source: path,
+    });
+    Ok(Some(dest_path))
 }
 
 fn copy_non_template_file(
@@ -139,20 +249,25 @@ fn copy_non_template_file(
     path: &Path,
     file_name: &str,
     package_manager: &str,
-) -> Result<()> {
+    output: &mut Output,
+) -> Result<Option<PathBuf>> {
     // Skip package.json.*.tera files in root workspace that don't match the package manager
     if workspace.is_root && file_name.starts_with("package.json.") && file_name.ends_with(".tera") {
         let pm_suffix = file_name
             .trim_end_matches(".tera")
             .split('.')
-            .last()
+            .next_back()
             .unwrap_or("");
         if pm_suffix != package_manager && pm_suffix != "base" {
             log_debug(&format!(
                 "Skipping non-matching package.json template: {}",
                 file_name
             ));
-            return Ok(());
+            output.emit(Event::FileSkipped {
+                source: path,
+                reason: "package.json template for another package manager",
+            });
+            return Ok(None);
         }
     }
     log_debug(&format!("Copying non-template file: {}", file_name));
@@ -161,27 +276,38 @@ fn copy_non_template_file(
         .dest_path
         .join(path.strip_prefix(&workspace.source_path)?);
 
-    fs::create_dir_all(dest_path.parent().unwrap())?;
-    fs::copy(path, &dest_path).map_err(|e| {
-        anyhow::anyhow!(
-            "Failed to copy file from {} to {}: {}",
-            path.display(),
-            dest_path.display(),
-            e
-        )
-    })?;
+    output.copy(path, &dest_path)?;
 
     log_debug(&format!(
         "Copied file: {} -> {}",
         path.display(),
         dest_path.display()
     ));
-    Ok(())
+    output.emit(Event::FileWritten {
+        path: &dest_path,
+        source: path,
+    });
+    Ok(Some(dest_path))
+}
+
+/// Tera puts the actual problem in the error's source.
+fn render_error_message(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
 }
 
-pub fn get_workspaces(template_dir: &Path, project_dir: &Path) -> Vec<Workspace> {
-    // bare minimum workspaces
-    vec![
+/// The workspaces every project has, followed by the `added` ones.
+pub fn get_workspaces(
+    template_dir: &Path,
+    project_dir: &Path,
+    added: &[AddedWorkspace],
+) -> Vec<Workspace> {
+    let mut workspaces = vec![
         Workspace {
             name: "root".to_string(),
             source_path: template_dir.to_path_buf(),
@@ -218,6 +344,12 @@ pub fn get_workspaces(template_dir: &Path, project_dir: &Path) -> Vec<Workspace>
             dest_path: project_dir.join("packages/logger"),
             is_root: false,
         },
-        // Add more workspaces as needed
-    ]
+    ];
+    workspaces.extend(added.iter().map(|workspace| Workspace {
+        name: workspace.name.clone(),
+        source_path: blueprints_dir(template_dir, workspace.kind).join(&workspace.blueprint),
+        dest_path: project_dir.join(workspace.path()),
+        is_root: false,
+    }));
+    workspaces
 }